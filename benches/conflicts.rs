@@ -0,0 +1,42 @@
+// Membership tests on the conflicts of a tx of a complete conflict graph with T = 2000 txs:
+// Conflicts (a HashSet) against a linear scan of a Vec<TxId>, as Conflicts was stored before.
+// Run with: cargo bench --bench conflicts
+
+use outro_08::aux_types::{Conflicts, TxId};
+use std::collections::BTreeSet;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const T: u32 = 2000;
+const ITERATIONS: u32 = 20;
+
+fn time(mut run: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        run();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn report(name: &str, hash_set: Duration, vec: Duration) {
+    println!("{name}: HashSet {hash_set:?}, Vec {vec:?} ({:.1}x)", vec.as_secs_f64() / hash_set.as_secs_f64());
+}
+
+fn main() {
+    let txs = (0..T).map(TxId::from_u32).collect::<Vec<TxId>>();
+    let conflict_vec = txs[1..].to_vec();
+    let conflicts = Conflicts::new_from(&conflict_vec);
+
+    // contains on every tx, as in the conflict checks of a whole vision
+    let hash_set = time(|| for tx in &txs { black_box(conflicts.contains(black_box(tx))); });
+    let vec = time(|| for tx in &txs { black_box(conflict_vec.contains(black_box(tx))); });
+    report("contains x T", hash_set, vec);
+
+    // intersects_set with a liked set of one tx per step of elim, the worst case of the scan
+    let liked_sets = txs.iter()
+        .map(|tx| BTreeSet::from([*tx]))
+        .collect::<Vec<BTreeSet<TxId>>>();
+    let hash_set = time(|| for liked in &liked_sets { black_box(conflicts.intersects_set(black_box(liked))); });
+    let vec = time(|| for liked in &liked_sets { black_box(conflict_vec.iter().any(|tx| liked.contains(tx))); });
+    report("intersects_set x T", hash_set, vec);
+}
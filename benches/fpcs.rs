@@ -0,0 +1,31 @@
+// Full runs of the default benchmark set: Database::run_benchmark with the default config 
+// on each tx graph of BENCH_TX_GRAPHS, averaged over a few seeds.
+// Run with: cargo bench --bench fpcs
+
+use outro_08::config::FpcsConfig;
+use outro_08::constants::BENCH_TX_GRAPHS;
+use outro_08::fpcs::{Database, RunOutcome};
+use std::time::Duration;
+
+const SEEDS: u64 = 10;
+const MAX_ROUNDS: usize = 1000;
+
+fn main() {
+    let config = FpcsConfig::default();
+    for tx_graph_type in BENCH_TX_GRAPHS {
+        let mut elapsed = Duration::ZERO;
+        let mut total_query_count = 0;
+        let mut rounds = 0;
+        for seed in 0..SEEDS {
+            let result = Database::run_benchmark(&config, tx_graph_type.clone(), seed, MAX_ROUNDS).unwrap();
+            let RunOutcome::Converged(run_rounds) = result.outcome else {
+                panic!("{tx_graph_type:?}, seed {seed}: no finality after {MAX_ROUNDS} rounds");
+            };
+            elapsed += result.elapsed;
+            total_query_count += result.total_query_count;
+            rounds += run_rounds;
+        }
+        println!("{tx_graph_type:?}: {:?} per run, {:.1} rounds, {} queries", 
+            elapsed / SEEDS as u32, rounds as f64 / SEEDS as f64, total_query_count / SEEDS);
+    }
+}
@@ -0,0 +1,445 @@
+use crate::aux_types::{NodeId, TxId, Vision};
+use crate::fpcs::{Database, Node, NodeType};
+use rand::seq::SliceRandom;
+use rand_core::RngCore;
+use std::any::Any;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+// Behavior of a malicious node when it is queried.
+// All the malicious nodes of a database share the same strategy instance.
+//
+// response: the opinion ("like" or not) reported to querier about tx, in the given round, 
+// or None to abstain (the query then gets no response on tx, as from a node without an opinion on it)
+// setup: called once when the database is generated, before any round, 
+// with the common vision of the nodes and the rng of the database
+// plan: called once at the beginning of each round, before any query, to coordinate the malicious nodes;
+// the plan is available to response through Database::adversary_plan (no plan by default)
+
+pub trait AdversaryStrategy: AsAny + Debug + Send + Sync {
+    fn response(&self, querier: &Node, tx: TxId, round: u32, db: &Database) -> Option<bool>;
+
+    fn setup(&mut self, _vision: &Vision, _rng: &mut dyn RngCore) {}
+
+    fn plan(&self, _round: u32, _db: &Database) -> Option<AdversaryController> { None }
+}
+
+// Response plan shared by all the malicious nodes in a round: the honest nodes are split in two sides,
+// and each side is pushed towards one of two conflicting txs (and away from their other conflicts).
+//
+// round: the round of the plan
+// targets: the two conflicting txs
+// sides: for each honest node, true if it is pushed towards targets.0, false if towards targets.1
+// others: the other conflicts of the targets, disliked towards every honest node
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdversaryController {
+    pub round: u32,
+    pub targets: (TxId, TxId),
+    pub sides: BTreeMap<NodeId, bool>,
+    pub others: BTreeSet<TxId>,
+}
+
+impl AdversaryController {
+    // Planned response to querier about tx, None if the plan does not cover it
+    pub fn response(&self, querier: NodeId, tx: TxId) -> Option<bool> {
+        let side = *self.sides.get(&querier)?;
+        if tx == self.targets.0 {
+            Some(side)
+        } else if tx == self.targets.1 {
+            Some(!side)
+        } else if self.others.contains(&tx) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+// Gives access to the concrete type of a strategy, implemented for every strategy
+pub trait AsAny {
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// Answers each query with the querying node's own opinion, abstaining if it has none
+#[derive(Debug, Clone, Copy)]
+pub struct EchoQuerier;
+
+impl AdversaryStrategy for EchoQuerier {
+    fn response(&self, querier: &Node, tx: TxId, _round: u32, _db: &Database) -> Option<bool> {
+        querier.vision.query_opinion(&tx)
+    }
+}
+
+// Answers each query with the opposite of the querying node's own opinion, abstaining if it has none
+#[derive(Debug, Clone, Copy)]
+pub struct AlwaysOppose;
+
+impl AdversaryStrategy for AlwaysOppose {
+    fn response(&self, querier: &Node, tx: TxId, _round: u32, _db: &Database) -> Option<bool> {
+        let opinion = querier.vision.try_get_opinion_status(&tx)?;
+        if opinion.is_like() {
+            Some(false)
+        } else if opinion.is_dislike() {
+            Some(true)
+        } else {
+            None
+        }
+    }
+}
+
+// Answers with the opinion of the majority of the honest nodes up to flip_round (inclusive),
+// and with the opposite of the majority opinion afterwards
+#[derive(Debug, Clone, Copy)]
+pub struct MimicMajorityThenFlip {
+    pub flip_round: u32,
+}
+
+impl AdversaryStrategy for MimicMajorityThenFlip {
+    fn response(&self, _querier: &Node, tx: TxId, round: u32, db: &Database) -> Option<bool> {
+        let majority = honest_majority_likes(tx, db);
+        Some(if round <= self.flip_round { majority } else { !majority })
+    }
+}
+
+// For the target tx, answers "like" to half of the honest nodes and "dislike" to the other half,
+// and echoes the querying node's own opinion for every other tx (abstaining if it has none).
+// The halves are defined by the position of the querying node among the honest nodes (ordered by id).
+// If no target is given, a tx with at least one conflict is chosen at random in setup.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Equivocate {
+    pub target: Option<TxId>,
+}
+
+impl AdversaryStrategy for Equivocate {
+    fn response(&self, querier: &Node, tx: TxId, _round: u32, db: &Database) -> Option<bool> {
+        if self.target != Some(tx) {
+            return querier.vision.query_opinion(&tx);
+        }
+        let querier_index = db.node_set.iter()
+            .filter(|(_, node_type, _)| *node_type == NodeType::Regular)
+            .position(|(id, _, _)| *id == querier.id)
+            .unwrap_or(0);
+        Some(querier_index % 2 == 0)
+    }
+
+    fn setup(&mut self, vision: &Vision, rng: &mut dyn RngCore) {
+        if self.target.is_none() {
+            let conflicting_txs = vision.get_txs().iter()
+                .filter(|tx| !vision.get_conflict_set(tx).get().is_empty())
+                .collect::<Vec<TxId>>();
+            self.target = conflicting_txs.choose(rng).copied();
+        }
+    }
+}
+
+// Finds the leading tx, i.e. the tx liked by most honest nodes among the txs the querying node 
+// has not finalized (ties go to the smallest TxId), and answers "dislike" for it and "like" 
+// for its conflicts, to keep any tx from collecting L consecutive agreements.
+// Every other tx gets the querying node's own opinion (or an abstention if it has none).
+#[derive(Debug, Clone, Copy)]
+pub struct OpposeLeader;
+
+impl AdversaryStrategy for OpposeLeader {
+    fn response(&self, querier: &Node, tx: TxId, _round: u32, db: &Database) -> Option<bool> {
+        let Some(leader) = honest_leader(querier, db) else {
+            return querier.vision.query_opinion(&tx);
+        };
+        if tx == leader {
+            Some(false)
+        } else if querier.vision.get_conflict_set(&leader).get().contains(&tx) {
+            Some(true)
+        } else {
+            querier.vision.query_opinion(&tx)
+        }
+    }
+}
+
+// Coordinated split: each round, the malicious nodes pick the pair of conflicting txs, not finalized 
+// by all honest nodes, with the most honest likes together (ties go to the smallest pair), 
+// and split the honest nodes in two halves, pushed towards each of them, to keep the network evenly split.
+// The half pushed towards the first tx is filled with the nodes that like it first, then with the ones
+// that like neither, in order of id. 
+// Txs outside the pair get the querying node's own opinion (or an abstention if it has none), 
+// or a dislike if they conflict with the pair.
+#[derive(Debug, Clone, Copy)]
+pub struct CoordinatedSplit;
+
+impl AdversaryStrategy for CoordinatedSplit {
+    fn response(&self, querier: &Node, tx: TxId, _round: u32, db: &Database) -> Option<bool> {
+        db.adversary_plan()
+            .and_then(|plan| plan.response(querier.id, tx))
+            .or_else(|| querier.vision.query_opinion(&tx))
+    }
+
+    fn plan(&self, round: u32, db: &Database) -> Option<AdversaryController> {
+        let honest_nodes = db.data.values()
+            .filter(|node| node.node_type == NodeType::Regular)
+            .collect::<Vec<&Node>>();
+        let likes = |tx: &TxId| honest_nodes.iter()
+            .filter(|node| node.vision.query_opinion(tx) == Some(true))
+            .count();
+        let finalized = |tx: &TxId| honest_nodes.iter()
+            .all(|node| node.vision.try_get_opinion_status(tx).is_some_and(|opinion| opinion.is_final()));
+
+        let vision = honest_nodes.first()?.vision.clone();
+        let targets = vision.get_txs().into_iter()
+            .filter(|tx| !finalized(tx))
+            .flat_map(|tx| vision.get_conflict_set(&tx).sorted_iter()
+                .filter(|conflict| tx < **conflict && !finalized(conflict))
+                .map(|conflict| (tx, *conflict))
+                .collect::<Vec<(TxId, TxId)>>())
+            // max_by_key returns the last maximum, so pairs are visited from the largest one
+            .rev()
+            .max_by_key(|(a, b)| likes(a) + likes(b))?;
+
+        // nodes that like the first target, then the ones that like neither, then the ones that like the second
+        let mut ordered_nodes = honest_nodes.iter()
+            .map(|node| {
+                let preference = match (node.vision.query_opinion(&targets.0), node.vision.query_opinion(&targets.1)) {
+                    (Some(true), _) => 0,
+                    (_, Some(true)) => 2,
+                    _ => 1,
+                };
+                (preference, node.id)
+            })
+            .collect::<Vec<(u8, NodeId)>>();
+        ordered_nodes.sort();
+        let half = ordered_nodes.len().div_ceil(2);
+        let sides = ordered_nodes.iter().enumerate()
+            .map(|(index, (_, id))| (*id, index < half))
+            .collect();
+
+        let others = vision.get_conflict_set(&targets.0).iter()
+            .chain(vision.get_conflict_set(&targets.1).iter())
+            .filter(|tx| **tx != targets.0 && **tx != targets.1)
+            .copied()
+            .collect();
+        Some(AdversaryController { round, targets, sides, others })
+    }
+}
+
+// Answers with a given function of the id of the querying node, the tx and the round, 
+// so each querier can be shown a different opinion on the same tx in the same round 
+// (e.g. "like" to some nodes and "dislike" to the others, to split the network).
+// Like any custom strategy, it cannot be serialized.
+#[derive(Clone)]
+pub struct PerRecipient(Arc<dyn Fn(NodeId, TxId, u32) -> bool + Send + Sync>);
+
+impl PerRecipient {
+    pub fn new(response: impl Fn(NodeId, TxId, u32) -> bool + Send + Sync + 'static) -> Self {
+        Self(Arc::new(response))
+    }
+}
+
+impl Debug for PerRecipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PerRecipient")
+    }
+}
+
+impl AdversaryStrategy for PerRecipient {
+    fn response(&self, querier: &Node, tx: TxId, round: u32, _db: &Database) -> Option<bool> {
+        Some((self.0)(querier.id, tx, round))
+    }
+}
+
+// Tx liked by most honest nodes among the txs that querier has not finalized, if any
+fn honest_leader(querier: &Node, db: &Database) -> Option<TxId> {
+    let honest_nodes = db.data.values()
+        .filter(|node| node.node_type == NodeType::Regular)
+        .collect::<Vec<&Node>>();
+    querier.vision.get_txs().into_iter()
+        .filter(|tx| !querier.vision.get_opinion_status(tx).is_final())
+        .map(|tx| {
+            let likes = honest_nodes.iter()
+                .filter(|node| node.vision.query_opinion(&tx) == Some(true))
+                .count();
+            (tx, likes)
+        })
+        // max_by_key returns the last maximum, so txs are visited from the largest TxId
+        .rev()
+        .max_by_key(|(_, likes)| *likes)
+        .map(|(tx, _)| tx)
+}
+
+// Whether the majority of the honest nodes with an opinion on tx currently likes it
+fn honest_majority_likes(tx: TxId, db: &Database) -> bool {
+    let responses = db.data.values()
+        .filter(|node| node.node_type == NodeType::Regular)
+        .filter_map(|node| node.vision.query_opinion(&tx))
+        .collect::<Vec<bool>>();
+    let likes = responses.iter().filter(|like| **like).count();
+    2*likes > responses.len()
+}
+
+// Serialization of the built-in strategies, used by NodeType. Custom strategies cannot be serialized.
+#[cfg(feature = "serde")]
+pub(crate) mod serde_strategy {
+    use super::{AdversaryStrategy, AlwaysOppose, CoordinatedSplit, EchoQuerier, Equivocate, MimicMajorityThenFlip, OpposeLeader};
+    use crate::aux_types::TxId;
+    use serde::{ser, Deserialize, Deserializer, Serialize, Serializer};
+    use std::sync::Arc;
+
+    #[derive(Serialize, Deserialize)]
+    enum BuiltinStrategy {
+        EchoQuerier,
+        AlwaysOppose,
+        MimicMajorityThenFlip { flip_round: u32 },
+        Equivocate { target: Option<TxId> },
+        OpposeLeader,
+        CoordinatedSplit,
+    }
+
+    pub fn serialize<S: Serializer>(strategy: &Arc<dyn AdversaryStrategy>, serializer: S) -> Result<S::Ok, S::Error> {
+        let strategy = (**strategy).as_any();
+        let builtin = if strategy.is::<EchoQuerier>() {
+            BuiltinStrategy::EchoQuerier
+        } else if strategy.is::<AlwaysOppose>() {
+            BuiltinStrategy::AlwaysOppose
+        } else if let Some(MimicMajorityThenFlip { flip_round }) = strategy.downcast_ref() {
+            BuiltinStrategy::MimicMajorityThenFlip { flip_round: *flip_round }
+        } else if let Some(Equivocate { target }) = strategy.downcast_ref() {
+            BuiltinStrategy::Equivocate { target: *target }
+        } else if strategy.is::<OpposeLeader>() {
+            BuiltinStrategy::OpposeLeader
+        } else if strategy.is::<CoordinatedSplit>() {
+            BuiltinStrategy::CoordinatedSplit
+        } else {
+            return Err(ser::Error::custom("custom adversary strategies cannot be serialized"));
+        };
+        builtin.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Arc<dyn AdversaryStrategy>, D::Error> {
+        let strategy: Arc<dyn AdversaryStrategy> = match BuiltinStrategy::deserialize(deserializer)? {
+            BuiltinStrategy::EchoQuerier => Arc::new(EchoQuerier),
+            BuiltinStrategy::AlwaysOppose => Arc::new(AlwaysOppose),
+            BuiltinStrategy::MimicMajorityThenFlip { flip_round } => Arc::new(MimicMajorityThenFlip { flip_round }),
+            BuiltinStrategy::Equivocate { target } => Arc::new(Equivocate { target }),
+            BuiltinStrategy::OpposeLeader => Arc::new(OpposeLeader),
+            BuiltinStrategy::CoordinatedSplit => Arc::new(CoordinatedSplit),
+        };
+        Ok(strategy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aux_types::TxGraphType;
+    use crate::builder::DatabaseBuilder;
+    use crate::config::FpcsConfig;
+    use crate::fpcs::RunOutcome;
+
+    const SEEDS: std::ops::Range<u64> = 0..10;
+
+    #[test]
+    fn oppose_leader_minority_does_not_prevent_finality() {
+        for seed in SEEDS {
+            let mut database = DatabaseBuilder::new()
+                .config(&FpcsConfig::default())
+                .malicious(1)
+                .malicious_strategy(OpposeLeader)
+                .seed(seed)
+                .build()
+                .unwrap();
+            assert!(database.is_within_safety_bound());
+
+            let outcome = database.run_until_final(100);
+            assert!(matches!(outcome, RunOutcome::Converged(_)), "seed {seed}: {outcome:?}");
+            assert!(database.has_consensus(), "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn echoing_strategies_abstain_towards_observers() {
+        let database = DatabaseBuilder::new()
+            .config(&FpcsConfig::default())
+            .malicious(2)
+            .observers(2)
+            .seed(0)
+            .build()
+            .unwrap();
+        let strategies: [&dyn AdversaryStrategy; 4] = [&EchoQuerier, &AlwaysOppose, &Equivocate::default(), &CoordinatedSplit];
+
+        // observers start without opinions, so there is nothing to echo
+        let observers = database.data.values()
+            .filter(|node| node.node_type == NodeType::Observer)
+            .collect::<Vec<&Node>>();
+        assert_eq!(observers.len(), 2);
+        for observer in observers {
+            for tx in database.tx_ids() {
+                for strategy in strategies {
+                    assert_eq!(strategy.response(observer, tx, 1, &database), None, "{strategy:?} on {tx}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn coordinated_split_delays_but_does_not_break_finality() {
+        let mut coordinated_rounds = 0;
+        let mut baseline_rounds = 0;
+        for seed in SEEDS {
+            let builder = || DatabaseBuilder::new()
+                .config(&FpcsConfig::default())
+                .malicious(1)
+                .seed(seed);
+            let mut coordinated = builder().malicious_strategy(CoordinatedSplit).build().unwrap();
+            let mut baseline = builder().build().unwrap();
+            assert!(coordinated.is_within_safety_bound());
+
+            let RunOutcome::Converged(rounds) = coordinated.run_until_final(100) else {
+                panic!("seed {seed}: the coordinated adversary prevented finality");
+            };
+            assert!(coordinated.has_consensus(), "seed {seed}");
+            coordinated_rounds += rounds;
+            let RunOutcome::Converged(rounds) = baseline.run_until_final(100) else {
+                panic!("seed {seed}: no finality without coordination");
+            };
+            baseline_rounds += rounds;
+        }
+        assert!(coordinated_rounds > baseline_rounds, "{coordinated_rounds} rounds with coordination, {baseline_rounds} without");
+    }
+
+    #[test]
+    fn per_recipient_split_stalls_finality() {
+        // 8 malicious nodes out of 20 push even ids towards tx 0 and odd ids towards tx 1
+        let split = PerRecipient::new(|querier, tx, _| (querier.get_u32() % 2 == 0) == (tx == TxId::from_u32(0)));
+        let mut stalled_runs = 0;
+        for seed in 0..20 {
+            let builder = || DatabaseBuilder::new()
+                .total_nodes(20)
+                .malicious(8)
+                .tx_count(2)
+                .tx_graph(TxGraphType::Complete)
+                .sequential_ids(true)
+                .seed(seed);
+            let mut attacked = builder().malicious_strategy(split.clone()).build().unwrap();
+            let mut baseline = builder().build().unwrap();
+            assert!(!attacked.is_within_safety_bound());
+
+            let querier = attacked.data.keys().copied().next().unwrap();
+            let like_tx_0 = querier.get_u32() % 2 == 0;
+            let node = &attacked.data[&querier];
+            assert_eq!(split.response(node, TxId::from_u32(0), 1, &attacked), Some(like_tx_0));
+            assert_eq!(split.response(node, TxId::from_u32(1), 1, &attacked), Some(!like_tx_0));
+
+            if let RunOutcome::Stalled(_) = attacked.run_until_final(50) {
+                stalled_runs += 1;
+            }
+            assert!(attacked.check_safety().is_ok(), "seed {seed}");
+            let outcome = baseline.run_until_final(50);
+            assert!(matches!(outcome, RunOutcome::Converged(_)), "seed {seed}: {outcome:?}");
+        }
+        assert!(stalled_runs >= 10, "the split stalled only {stalled_runs} of 20 runs");
+    }
+}
@@ -1,55 +1,89 @@
-use std::collections::BTreeMap;
+use std::collections::{hash_set, BTreeMap, BTreeSet, HashSet, VecDeque};
 use std::cmp::Ordering;
-use std::hash::{DefaultHasher, Hash, Hasher};
-use rand_core::{OsRng, RngCore};
+use std::fmt;
+use rand::Rng;
+use rand_core::RngCore;
+use thiserror::Error;
 
+// Serialized as a sorted sequence, so that the serialization of a database is deterministic
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone)]
-pub struct Conflicts(Vec<TxId>);
+pub struct Conflicts(#[cfg_attr(feature = "serde", serde(serialize_with = "serialize_sorted"))] HashSet<TxId>);
 
+// Iterates over the conflicts in an unspecified order; see Conflicts::sorted_iter for a deterministic one
 pub struct ConflictsIterator<'a> {
-    conflicts: &'a Conflicts,
-    index: usize,
+    inner: hash_set::Iter<'a, TxId>,
 }
 
 impl<'a> Iterator for ConflictsIterator<'a> {
     type Item = &'a TxId;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index < self.conflicts.0.len() {
-            let result = Some(&self.conflicts.0[self.index]);
-            self.index += 1;
-            result
-        } else {
-            None
-        }
+        self.inner.next()
+    }
+}
+
+impl Default for Conflicts {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Conflicts {
-    pub fn get(&self) -> &Vec<TxId> {
+    pub fn get(&self) -> &HashSet<TxId> {
         &self.0
     }
+    pub fn contains(&self, tx: &TxId) -> bool {
+        self.0.contains(tx)
+    }
     pub fn add(&mut self, tx: TxId) {
-        self.0.push(tx);
+        self.0.insert(tx);
     }
     pub fn new() -> Self {
-        Self(Vec::new())
+        Self(HashSet::new())
     }
-    pub fn new_from(txs: &Vec<TxId>) -> Self {
-        Self(txs.clone())
+    pub fn new_from(txs: &[TxId]) -> Self {
+        Self(txs.iter().copied().collect())
     }
-    pub fn iter(&self) -> ConflictsIterator {
+    pub fn iter(&self) -> ConflictsIterator<'_> {
         ConflictsIterator {
-            conflicts: self,
-            index: 0,
+            inner: self.0.iter(),
         }
     }
-    
+    // Conflicts in increasing order, for when the order matters (e.g. to keep a simulation deterministic)
+    pub fn sorted_iter(&self) -> std::vec::IntoIter<&TxId> {
+        let mut conflicts = self.0.iter().collect::<Vec<&TxId>>();
+        conflicts.sort();
+        conflicts.into_iter()
+    }
+    // Checks if any of the conflicts is in the given set,
+    // looking up the elements of the smaller set in the larger one
+    pub fn intersects_set(&self, liked: &BTreeSet<TxId>) -> bool {
+        if self.0.len() <= liked.len() {
+            self.0.iter().any(|tx| liked.contains(tx))
+        } else {
+            liked.iter().any(|tx| self.0.contains(tx))
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+fn serialize_sorted<S: serde::Serializer>(conflicts: &HashSet<TxId>, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut conflicts = conflicts.iter().collect::<Vec<&TxId>>();
+    conflicts.sort();
+    serializer.collect_seq(conflicts)
 }
 
-#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone)]
 pub struct Neighborhood(Vec<NodeId>);
 
+impl Default for Neighborhood {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Neighborhood {
     pub fn get(&self) -> &Vec<NodeId> {
         &self.0
@@ -57,16 +91,22 @@ impl Neighborhood {
     pub fn add(&mut self, node: NodeId) {
         self.0.push(node);
     }
+    pub fn remove(&mut self, node: &NodeId) {
+        self.0.retain(|id| id != node);
+    }
     pub fn new() -> Self {
         Self(Vec::new())
     }
-    pub fn set_new(nodes: &Vec<NodeId>) -> Self {
-        Self(nodes.clone())
+    pub fn set_new(nodes: &[NodeId]) -> Self {
+        Self(nodes.to_vec())
     }
     pub fn len(&self) -> usize {
         self.0.len()
     }
-    pub fn iter(&self) -> NeighborhoodIterator {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    pub fn iter(&self) -> NeighborhoodIterator<'_> {
         NeighborhoodIterator {
             neighborhood: self,
             index: 0,
@@ -74,33 +114,57 @@ impl Neighborhood {
     }
     // Samples k nodes from the neighborhood and returns them as a vector of ids.
     // if k is larger than the neighborhood size, it returns all the neighborhood.
-    // The sampling is done without repetition
-    pub fn sample(&self, k: usize) -> Vec<NodeId> {
-        let neighborhood = self.get().clone();
+    // The sampling is done without repetition, with a partial Fisher-Yates shuffle:
+    // it draws exactly k random numbers, so it always terminates.
+    pub fn sample(&self, k: usize, rng: &mut impl RngCore) -> Vec<NodeId> {
+        let mut neighborhood = self.get().clone();
         let neighborhood_size = neighborhood.len();
 
         if k >= neighborhood_size {
             return neighborhood;
         }
 
-        let mut sample = Vec::new();        
-        while sample.len() < k {
-            let r = OsRng.next_u64();
-
-            let index = ((r as u128 * neighborhood_size as u128)/(u64::max_value() as u128)) as usize;
-            if !&sample.contains(&neighborhood[index]) {
-                sample.push(neighborhood[index]);
+        // after step i, the first i+1 positions hold a uniform sample of the neighborhood
+        for i in 0..k {
+            let j = rng.gen_range(i..neighborhood_size);
+            neighborhood.swap(i, j);
+        }
+        neighborhood.truncate(k);
+
+        neighborhood
+    }
+
+    // Samples up to k distinct nodes from the neighborhood, each draw picking a remaining node 
+    // with probability proportional to its weight (weighted sampling without replacement).
+    // Nodes with weight 0, or without an entry in weights, are never picked, so fewer than k 
+    // nodes are returned when there are fewer than k nodes with positive weight.
+    // With equal weights, this is the same distribution as sample.
+    pub fn sample_weighted(&self, k: usize, weights: &BTreeMap<NodeId, u64>, rng: &mut impl RngCore) -> Vec<NodeId> {
+        let mut candidates = self.0.iter()
+            .map(|id| (*id, weights.get(id).copied().unwrap_or(0)))
+            .filter(|(_, weight)| *weight > 0)
+            .collect::<Vec<(NodeId, u64)>>();
+        let mut total_weight = candidates.iter().map(|(_, weight)| *weight as u128).sum::<u128>();
+
+        let mut sample = Vec::new();
+        while sample.len() < k && !candidates.is_empty() {
+            let mut r = rng.gen_range(0..total_weight);
+            let mut index = 0;
+            while r >= candidates[index].1 as u128 {
+                r -= candidates[index].1 as u128;
+                index += 1;
             }
+            let (id, weight) = candidates.remove(index);
+            total_weight -= weight as u128;
+            sample.push(id);
         }
 
         sample
     }
 
-
-
 }
 
-struct NeighborhoodIterator<'a> {
+pub struct NeighborhoodIterator<'a> {
     neighborhood: &'a Neighborhood,
     index: usize,
 }
@@ -119,24 +183,28 @@ impl<'a> Iterator for NeighborhoodIterator<'a> {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone)]
 pub struct Vision(BTreeMap<TxId, (Conflicts, Opinion)>);
 
+impl Default for Vision {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Vision {
 
     pub fn has_finalized(&self) -> bool {
-        let unfinalized_txs = self.0
-            .values().cloned()  
-            .filter(|(_, opinion)| !opinion.is_final() )
-            .collect::<Vec<(Conflicts, Opinion)>>();
-
-        unfinalized_txs.len() == 0
+        self.0
+            .values()
+            .all(|(_, opinion)| opinion.is_final() )
     }
 
     pub fn get_conflict_set(&self, tx: &TxId) -> &Conflicts {
         // nodes will never try to access an unknown tx by design
         let (conflict_set, _) = self.0
-            .get(&tx).expect("unknown TxId"); 
+            .get(tx).expect("unknown TxId"); 
 
         conflict_set
     }
@@ -155,10 +223,33 @@ impl Vision {
         opinion.is_like()
     }
 
+    // Opinion on tx; None if the tx is unknown
+    pub fn try_get_opinion_status(&self, tx: &TxId) -> Option<&Opinion> {
+        self.0.get(tx).map(|(_, opinion)| opinion)
+    }
+
+    // Answer to a query on tx: None if the tx is unknown or there is no opinion on it (an abstention)
+    pub fn query_opinion(&self, tx: &TxId) -> Option<bool> {
+        match self.0.get(tx) {
+            Some((_, Opinion::Pending(like, _))) | Some((_, Opinion::Final(like))) => Some(*like),
+            _ => None,
+        }
+    }
+
+    // Same as query_opinion, but a pending opinion is only given once it has at least min_confidence
+    // consecutive agreements (abstaining otherwise); final opinions are always given
+    pub fn query_confident_opinion(&self, tx: &TxId, min_confidence: u32) -> Option<bool> {
+        match self.0.get(tx) {
+            Some((_, Opinion::Pending(like, confidence))) if *confidence >= min_confidence => Some(*like),
+            Some((_, Opinion::Final(like))) => Some(*like),
+            _ => None,
+        }
+    }
+
     pub fn get_mut_opinion(&mut self, tx: &TxId) -> &mut Opinion {
         // nodes will never try to access an unknown tx by design
         let (_, opinion) = self.0
-            .get_mut(&tx).expect("unknown TxId"); 
+            .get_mut(tx).expect("unknown TxId"); 
 
         opinion
     }
@@ -166,7 +257,7 @@ impl Vision {
     pub fn get_opinion_status(&self, tx: &TxId) -> &Opinion {
         // nodes will never try to access an unknown tx by design
         let (_, opinion) = self.0
-            .get(&tx).expect("unknown TxId"); 
+            .get(tx).expect("unknown TxId"); 
 
         opinion
     }   
@@ -181,14 +272,117 @@ impl Vision {
 
     pub fn set_opinion(&mut self, tx: &TxId, new_opinion: Opinion) {
         // nodes will never try to access an unknown tx by design
-        let old_opinion = self.get_mut_opinion(&tx); 
+        let old_opinion = self.get_mut_opinion(tx); 
         *old_opinion = new_opinion;
     }
 
+    // Checks that the conflicts are symmetric: if tx lists conflict, conflict is known and lists tx
+    pub fn validate_symmetric_conflicts(&self) -> Result<(), Asymmetry> {
+        for (tx, (conflicts, _)) in &self.0 {
+            for conflict in conflicts.sorted_iter() {
+                let symmetric = self.0.get(conflict)
+                    .is_some_and(|(conflict_set, _)| conflict_set.get().contains(tx));
+                if !symmetric {
+                    return Err(Asymmetry { tx: *tx, conflict: *conflict });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Txs currently liked (Pending or Final), in increasing order
+    pub fn current_liked_set(&self) -> Vec<TxId> {
+        self.0.iter()
+            .filter(|(_, (_, opinion))| opinion.is_like())
+            .map(|(tx, _)| *tx)
+            .collect()
+    }
+
+    // Checks that no two liked txs conflict with each other
+    pub fn liked_set_is_independent(&self) -> bool {
+        let liked_set = self.current_liked_set().into_iter().collect::<BTreeSet<TxId>>();
+        liked_set.iter()
+            .all(|tx| !self.get_conflict_set(tx).intersects_set(&liked_set))
+    }
+
+    // Checks that the liked set is a maximal independent set of the conflict graph:
+    // it is independent and every tx that is not liked conflicts with a liked tx
+    pub fn is_maximal_independent(&self) -> bool {
+        let liked_set = self.current_liked_set().into_iter().collect::<BTreeSet<TxId>>();
+        self.0.iter().all(|(tx, (conflicts, _))| conflicts.intersects_set(&liked_set) != liked_set.contains(tx))
+    }
+
+    pub fn contains(&self, tx: &TxId) -> bool {
+        self.0.contains_key(tx)
+    }
+
+    // Connected components of the conflict graph (a tx without conflicts is a component by itself),
+    // each sorted by TxId, in order of their smallest TxId.
+    // Conflicts with unknown txs are ignored.
+    pub fn connected_components(&self) -> Vec<Vec<TxId>> {
+        let mut visited = BTreeSet::new();
+        let mut components = Vec::new();
+        for tx in self.0.keys() {
+            if !visited.insert(*tx) { continue; }
+            let mut component = vec![*tx];
+            let mut queue = VecDeque::from([*tx]);
+            while let Some(current) = queue.pop_front() {
+                for conflict in self.get_conflict_set(&current).iter() {
+                    if self.contains(conflict) && visited.insert(*conflict) {
+                        component.push(*conflict);
+                        queue.push_back(*conflict);
+                    }
+                }
+            }
+            component.sort();
+            components.push(component);
+        }
+        components
+    }
+
+    // Same vision with each tx renamed as given in labels (txs without a label keep their id)
+    pub fn relabel(&self, labels: &BTreeMap<TxId, TxId>) -> Vision {
+        let label = |tx: &TxId| labels.get(tx).copied().unwrap_or(*tx);
+        Self(self.0.iter()
+            .map(|(tx, (conflicts, opinion))| {
+                let conflicts = conflicts.iter().map(label).collect::<Vec<TxId>>();
+                (label(tx), (Conflicts::new_from(&conflicts), *opinion))
+            })
+            .collect())
+    }
+
+    // Keeps only the txs for which keep returns true; the conflict sets are left as they are,
+    // so they may list txs that are not known, as for txs received by gossip
+    pub fn retain(&mut self, mut keep: impl FnMut(&TxId) -> bool) {
+        self.0.retain(|tx, _| keep(tx));
+    }
+
+    // Adds a new tx with the given conflicts and opinion,
+    // and adds it to the conflict sets of its known conflicts, so the conflicts stay symmetric
+    // (conflicts that are not known yet get tx in their conflict set when they are added)
+    pub fn add_tx(&mut self, tx: TxId, conflicts_with: &[TxId], opinion: Opinion) {
+        for conflict in conflicts_with {
+            if let Some((conflict_set, _)) = self.0.get_mut(conflict) {
+                conflict_set.add(tx);
+            }
+        }
+        self.0.insert(tx, (Conflicts::new_from(conflicts_with), opinion));
+    }
+
+}
+
+// tx lists conflict as a conflict, but conflict does not list tx (or is unknown)
+
+#[derive(Debug, Error, PartialEq)]
+#[error("{tx:?} conflicts with {conflict:?}, but {conflict:?} does not conflict with {tx:?}")]
+pub struct Asymmetry {
+    pub tx: TxId,
+    pub conflict: TxId,
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Opinion{
     None,
     Pending(bool, u32),
@@ -204,58 +398,118 @@ impl Opinion {
         }
     }
 
+    // Only an explicit dislike; no opinion is neither a like nor a dislike
+    pub fn is_dislike(&self) -> bool {
+        matches!(*self, Self::Pending(false, _) | Self::Final(false))
+    }
+
     pub fn is_none(&self) -> bool {
-        match *self {
-            Self::None => true,
-            _ => false
-        }        
+        matches!(*self, Self::None)
     }
 
     pub fn is_final(&self) -> bool {
-        match *self {
-            Self::Final(_) => true,
-            _ => false 
-        }
+        matches!(*self, Self::Final(_))
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, Copy)]
 pub enum NodeGraphType{
-    Complete
+    Complete,
+    // Erdős–Rényi graph, each edge exists with the given probability
+    Random(f64),
+    // Random graph where every node has (approximately) the given degree
+    Regular(usize),
+    // Watts-Strogatz graph: ring lattice of degree k with edges rewired with probability beta
+    SmallWorld { k: usize, beta: f64 },
+    // 2D torus of width x height nodes, each linked to its 4 neighbors (left, right, up and down, wrapping around)
+    Grid { width: usize, height: usize }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum TxGraphType{
     Complete,
-    Star
+    Star,
+    // tx i conflicts with tx i-1 and tx i+1
+    Path,
+    // tx i conflicts with tx i-1 and tx i+1 modulo the number of txs
+    Cycle,
+    // tx 0 conflicts with all the other txs, which also form a cycle among themselves
+    Wheel,
+    // Erdős–Rényi graph, each pair of txs conflicts with the given probability
+    RandomConflict(f64),
+    // disjoint complete conflict graphs, clusters of them, each with size txs
+    Clusters { clusters: usize, size: usize },
+    // complete bipartite graph: each of the left txs conflicts with each of the right txs, 
+    // and txs on the same side do not conflict
+    Bipartite { left: usize, right: usize },
+    // independent conflict graphs (shards) over disjoint sets of txs: no tx conflicts with a tx of another shard
+    Union(Vec<TxGraphSpec>)
 }
 
-pub fn intersects(vec1: &Vec<TxId>, vec2: &Vec<TxId>) -> bool {
-    for id in vec1 {
-        if (*vec2).contains(id) { return true }
-    }
-    false
+// A shard of TxGraphType::Union: its conflict graph type and its number of txs
+#[derive(Debug, Clone)]
+pub struct TxGraphSpec {
+    pub graph_type: TxGraphType,
+    pub tx_count: usize,
 }
 
+// Prefix of every hashed tx id, so that the hashes used to order txs 
+// are not shared with any other use of the same hash function
+const TX_ORDER_DOMAIN: &[u8] = b"FPCS/tx-order";
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+// Hash used to order the txs in elim and comp (see HashedTxId):
+//
+// Default: FNV-1a over the domain prefix, the id and the random number, so the order changes every round
+// Fnv: FNV-1a over the domain prefix and the id only, a pseudo-random order that is the same in every round,
+// so the tie-break order does not depend on the random number of the round
+// Identity: the raw id, so the txs are ordered by TxId
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OrderHash {
+    #[default]
+    Default,
+    Fnv,
+    Identity,
+}
+
+// Tx id combined with the random number of a round, ordered by its hash.
+// The hash is FNV-1a (64 bits) over fixed-size little-endian bytes (see OrderHash), 
+// so the order is the same across toolchains and platforms.
+// Hash ties fall back to the raw ids, so the order is a strict total order.
 #[derive(Clone, PartialEq, Debug, Copy)]
 pub struct HashedTxId {
     pub id: TxId,
     pub random_number: u32,
+    pub order_hash: OrderHash,
 }
 
-impl Ord for HashedTxId {
-    fn cmp(&self, other: &Self) -> Ordering {
-        let mut s = DefaultHasher::new();
-        self.id.get_u32().hash(&mut s);
-        self.random_number.hash(&mut s);
-        let self_hashed_id = s.finish();
+impl HashedTxId {
+    pub fn hash_value(&self) -> u64 {
+        match self.order_hash {
+            OrderHash::Default => fnv_1a(TX_ORDER_DOMAIN.iter()
+                .chain(&self.id.get_u32().to_le_bytes())
+                .chain(&self.random_number.to_le_bytes())),
+            OrderHash::Fnv => fnv_1a(TX_ORDER_DOMAIN.iter()
+                .chain(&self.id.get_u32().to_le_bytes())),
+            OrderHash::Identity => self.id.get_u32() as u64,
+        }
+    }
+}
 
-        let mut s = DefaultHasher::new();
-        other.id.get_u32().hash(&mut s);
-        other.random_number.hash(&mut s);
-        let other_hashed_id = s.finish();
+fn fnv_1a<'a>(bytes: impl Iterator<Item = &'a u8>) -> u64 {
+    bytes.fold(FNV_OFFSET_BASIS, |hash, byte| (hash ^ *byte as u64).wrapping_mul(FNV_PRIME))
+}
 
-        self_hashed_id.cmp(&other_hashed_id)
+impl Ord for HashedTxId {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.hash_value().cmp(&other.hash_value())
+            .then_with(|| self.id.cmp(&other.id))
+            .then_with(|| self.random_number.cmp(&other.random_number))
     }
 }
 
@@ -267,7 +521,8 @@ impl PartialOrd for HashedTxId {
 
 impl Eq for HashedTxId {}
 
-#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum NodeStatus{
     NotFinalized,
     Finalized,
@@ -279,6 +534,7 @@ impl NodeStatus {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TxGlobalStatus{
     NotFinalized,
@@ -291,12 +547,27 @@ impl TxGlobalStatus {
     }
 }
 
-#[derive(Clone, PartialEq, Debug, Eq, Copy, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, PartialEq, Debug, Eq, Hash, Copy, PartialOrd, Ord)]
 pub struct TxId(u32);
 
 impl TxId {
-    pub fn generate() -> TxId {
-        TxId(OsRng.next_u32())
+    pub fn generate(rng: &mut impl RngCore) -> TxId {
+        TxId(rng.next_u32())
+    }
+
+    // Generates n distinct ids, in the order they are drawn; an id that was already drawn is drawn again.
+    // Without collisions, this is the same as calling generate n times.
+    pub fn generate_batch(n: usize, rng: &mut impl RngCore) -> Vec<TxId> {
+        let mut drawn = BTreeSet::new();
+        let mut batch = Vec::with_capacity(n);
+        while batch.len() < n {
+            let tx = TxId::generate(rng);
+            if drawn.insert(tx) {
+                batch.push(tx);
+            }
+        }
+        batch
     }
     
     pub fn get_u32(&self) -> u32 {
@@ -308,6 +579,13 @@ impl TxId {
     }
 }
 
+// Short form of the id, for logs and exports: its low 6 hex digits (so different ids may render the same)
+impl fmt::Display for TxId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:06x}", self.0 & 0xff_ffff)
+    }
+}
+
 impl<'a> FromIterator<&'a TxId> for Vec<TxId>{
     fn from_iter<T: IntoIterator<Item = &'a TxId>>(iter: T) -> Self {
         let mut vec = Vec::new();
@@ -318,12 +596,28 @@ impl<'a> FromIterator<&'a TxId> for Vec<TxId>{
 
 
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, PartialEq, Debug, Ord, PartialOrd, Eq, Copy)]
 pub struct NodeId(u32);
 
 impl NodeId {
-    pub fn generate() -> NodeId {
-        NodeId(OsRng.next_u32())
+    pub fn generate(rng: &mut impl RngCore) -> NodeId {
+        NodeId(rng.next_u32())
+    }
+
+    pub fn get_u32(&self) -> u32 {
+        self.0
+    }
+
+    pub fn from_u32(n: u32) -> NodeId {
+        NodeId(n)
+    }
+}
+
+// Same short form as TxId
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:06x}", self.0 & 0xff_ffff)
     }
 }
 
@@ -333,4 +627,22 @@ impl<'a> FromIterator<&'a NodeId> for Vec<NodeId>{
         vec.extend(iter);
         vec        
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_display_their_low_six_hex_digits() {
+        assert_eq!(TxId::from_u32(0).to_string(), "000000");
+        assert_eq!(TxId::from_u32(0xab).to_string(), "0000ab");
+        assert_eq!(TxId::from_u32(0x12_abcdef).to_string(), "abcdef");
+        assert_eq!(TxId::from_u32(u32::MAX).to_string(), "ffffff");
+
+        assert_eq!(NodeId::from_u32(0).to_string(), "000000");
+        assert_eq!(NodeId::from_u32(0x12_abcdef).to_string(), "abcdef");
+        assert_eq!(NodeId::from_u32(u32::MAX).to_string(), "ffffff");
+        // the same id renders the same for txs and nodes
+        assert_eq!(NodeId::from_u32(0x345678).to_string(), TxId::from_u32(0x345678).to_string());
+    }
+}
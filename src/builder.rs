@@ -0,0 +1,166 @@
+use crate::adversary::{AdversaryStrategy, EchoQuerier};
+use crate::aux_types::{NodeGraphType, NodeId, Opinion, TxGraphType, TxId};
+use crate::config::FpcsConfig;
+use crate::fpcs::{Database, DatabaseError, LikeDistributions};
+use std::collections::BTreeMap;
+
+// Builder for a Database, an alternative to the positional arguments of Database::generate_new.
+// Fields that are not set default to FpcsConfig::default(), no faulty, malicious or observer nodes,
+// a complete node graph, a star conflict graph, equally distributed likes, 
+// malicious nodes that echo the querier and a random seed.
+
+pub struct DatabaseBuilder {
+    pub(crate) config: FpcsConfig,
+    pub(crate) faulty_node_count: usize,
+    pub(crate) malicious_node_count: usize,
+    pub(crate) observer_node_count: usize,
+    pub(crate) node_graph_type: NodeGraphType,
+    pub(crate) tx_graph_type: TxGraphType,
+    pub(crate) conflict_edges: Option<ConflictEdges>,
+    pub(crate) initial_distribution: LikeDistributions,
+    pub(crate) malicious_strategy: Box<dyn AdversaryStrategy>,
+    pub(crate) seed: Option<u64>,
+    pub(crate) sequential_ids: bool,
+    pub(crate) initial_confidence: u32,
+    pub(crate) tx_knowledge: f64,
+    pub(crate) initial_opinions: Option<InitialOpinions>,
+}
+
+// Explicit conflict graph: the txs and the conflicting pairs
+pub(crate) type ConflictEdges = (Vec<TxId>, Vec<(TxId, TxId)>);
+
+// Initial opinions of each honest node on each tx
+pub type InitialOpinions = BTreeMap<NodeId, BTreeMap<TxId, Opinion>>;
+
+// The builder validates its inputs exactly like Database::generate_new
+pub type BuildError = DatabaseError;
+
+impl Default for DatabaseBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DatabaseBuilder {
+    pub fn new() -> Self {
+        Self {
+            config: FpcsConfig::default(),
+            faulty_node_count: 0,
+            malicious_node_count: 0,
+            observer_node_count: 0,
+            node_graph_type: NodeGraphType::Complete,
+            tx_graph_type: TxGraphType::Star,
+            conflict_edges: None,
+            initial_distribution: LikeDistributions::Equal,
+            malicious_strategy: Box::new(EchoQuerier),
+            seed: None,
+            sequential_ids: false,
+            initial_confidence: 0,
+            tx_knowledge: 1.0,
+            initial_opinions: None,
+        }
+    }
+
+    // Replaces the whole FPCS configuration, including the node and tx counts
+    pub fn config(mut self, config: &FpcsConfig) -> Self {
+        self.config = *config;
+        self
+    }
+
+    pub fn total_nodes(mut self, n: usize) -> Self {
+        self.config.n = n;
+        self
+    }
+
+    pub fn faulty(mut self, f: usize) -> Self {
+        self.faulty_node_count = f;
+        self
+    }
+
+    pub fn malicious(mut self, m: usize) -> Self {
+        self.malicious_node_count = m;
+        self
+    }
+
+    // Observers count among the total nodes: they query but never vote, see NodeType::Observer
+    pub fn observers(mut self, o: usize) -> Self {
+        self.observer_node_count = o;
+        self
+    }
+
+    pub fn node_graph(mut self, node_graph_type: NodeGraphType) -> Self {
+        self.node_graph_type = node_graph_type;
+        self
+    }
+
+    pub fn tx_graph(mut self, tx_graph_type: TxGraphType) -> Self {
+        self.tx_graph_type = tx_graph_type;
+        self
+    }
+
+    pub fn tx_count(mut self, t: usize) -> Self {
+        self.config.t = t;
+        self
+    }
+
+    // Uses the explicit conflict graph given by txs and edges instead of the tx graph type,
+    // and sets the tx count to txs.len(), see Database::from_conflict_edges
+    pub fn conflict_edges(mut self, txs: &[TxId], edges: &[(TxId, TxId)]) -> Self {
+        self.config.t = txs.len();
+        self.conflict_edges = Some((txs.to_vec(), edges.to_vec()));
+        self
+    }
+
+    pub fn distribution(mut self, initial_distribution: LikeDistributions) -> Self {
+        self.initial_distribution = initial_distribution;
+        self
+    }
+
+    // Strategy followed by all malicious nodes
+    pub fn malicious_strategy(mut self, strategy: impl AdversaryStrategy + 'static) -> Self {
+        self.malicious_strategy = Box::new(strategy);
+        self
+    }
+
+    // Makes the simulation reproducible, see Database::generate_new_seeded
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    // Numbers nodes and txs 0, 1, 2, ... in order of creation instead of drawing random ids,
+    // so that the outputs of different runs can be compared (ids given in conflict_edges are kept)
+    pub fn sequential_ids(mut self, sequential_ids: bool) -> Self {
+        self.sequential_ids = sequential_ids;
+        self
+    }
+
+    // Starts the initial opinions of the honest nodes with initial_confidence consecutive agreements,
+    // e.g. Pending(true, initial_confidence), to warm-start a network that already has some confidence; 
+    // clamped to L-1, so that every tx still needs at least one round to be finalized
+    pub fn initial_confidence(mut self, initial_confidence: u32) -> Self {
+        self.initial_confidence = initial_confidence;
+        self
+    }
+
+    // Each honest node knows each tx (with its conflicts) with probability p, independently, 
+    // instead of knowing all of them; the other txs reach it with Database::propagate_round
+    pub fn tx_knowledge(mut self, p: f64) -> Self {
+        self.tx_knowledge = p;
+        self
+    }
+
+    // Starts the honest nodes with exactly the given opinions instead of the initial distribution
+    // (which is then ignored); the txs a node is not given an opinion on start with Opinion::None. 
+    // The liked txs of each node must not conflict with each other, they are not repaired.
+    // The node ids are the ones of the database, so this is meant to be used with sequential_ids,
+    // where the honest nodes are 0, 1, 2, ..., see Database::from_initial_opinions
+    pub fn initial_opinions(mut self, opinions: InitialOpinions) -> Self {
+        self.initial_opinions = Some(opinions);
+        self
+    }
+
+    pub fn build(self) -> Result<Database, BuildError> {
+        Database::generate_from(self)
+    }
+}
@@ -0,0 +1,109 @@
+use crate::aux_types::OrderHash;
+use crate::constants::{BETA, K, L, N, T};
+use std::fmt;
+use std::sync::Arc;
+
+// Parameters of a FPCS simulation:
+//
+// k: number of queries sent by a node in each round
+// l: number of rounds with the same opinion before a tx is finalized
+// t: number of transactions
+// beta: FPCS parameter, bounds the random threshold to [beta, 1-beta]
+// n: number of nodes
+// weighted_sampling: if true, queries are sampled proportionally to the weight (stake) of the nodes
+// order_hash: hash that orders the txs in elim and comp (see OrderHash)
+// finalization_policy: when a tx is finalized globally, from the final opinions of the honest nodes (see FinalizationPolicy)
+// tie: whether a tx with exactly as many likes as the threshold is liked (see TieRule)
+// separate_order_random: if true, each round draws a second random number for the order of the txs in elim and comp,
+// independent of the one that sets the threshold; if false, the same random number is used for both
+// response_confidence: honest nodes only answer a query on a tx with a pending opinion once it has 
+// at least this number of consecutive agreements, and abstain otherwise (0: always answer)
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FpcsConfig {
+    pub k: usize,
+    pub l: u32,
+    pub t: usize,
+    pub beta: f64,
+    pub n: usize,
+    pub weighted_sampling: bool,
+    pub order_hash: OrderHash,
+    pub separate_order_random: bool,
+    pub response_confidence: u32,
+    pub tie: TieRule,
+    pub finalization_policy: FinalizationPolicy,
+}
+
+impl Default for FpcsConfig {
+    // Reproduces the values in constants.rs
+    fn default() -> Self {
+        Self {
+            k: K,
+            l: L,
+            t: T,
+            beta: BETA,
+            n: N,
+            weighted_sampling: false,
+            order_hash: OrderHash::Default,
+            separate_order_random: false,
+            response_confidence: 0,
+            tie: TieRule::Dislike,
+            finalization_policy: FinalizationPolicy::Unanimous,
+        }
+    }
+}
+
+// Outcome of a query round for a tx whose number of likes is exactly the threshold (see fpcs::threshold):
+//
+// Dislike: a tx is liked only if its likes exceed the threshold (likes > threshold), as in FPC,
+// so a tx with no responses is never liked
+// Like: a tx is liked if its likes reach the threshold (likes >= threshold), 
+// so a tx with no responses is always liked
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TieRule {
+    #[default]
+    Dislike,
+    Like,
+}
+
+// BETA to use in each round, given the index of the round (starting at 1), 
+// for protocols that change BETA over time (e.g. decreasing it).
+// Shared by the snapshots of a database.
+#[derive(Clone)]
+pub struct BetaSchedule(Arc<dyn Fn(u32) -> f64 + Send + Sync>);
+
+impl BetaSchedule {
+    pub fn new(schedule: impl Fn(u32) -> f64 + Send + Sync + 'static) -> Self {
+        Self(Arc::new(schedule))
+    }
+
+    pub fn beta(&self, round: u32) -> f64 {
+        (self.0)(round)
+    }
+}
+
+impl fmt::Debug for BetaSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BetaSchedule")
+    }
+}
+
+// When a tx is finalized globally (TxGlobalStatus::Finalized); each node still finalizes its own opinion on it 
+// (Opinion::Final) after L rounds of agreement, and only those final opinions count:
+//
+// Unanimous: all honest nodes have a final opinion on it
+// Threshold(f): at least the fraction f (in (0, 1]) of the honest nodes have a final opinion on it
+// StakeThreshold(f): the honest nodes with a final opinion on it hold at least the fraction f 
+// of the stake (weight) of the honest nodes
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum FinalizationPolicy {
+    #[default]
+    Unanimous,
+    Threshold(f64),
+    StakeThreshold(f64),
+}
@@ -1,5 +1,9 @@
+use crate::aux_types::TxGraphType;
+
 pub const K: usize = 5; // number of queries
 pub const L: u32 = 5; // number of rounds before tx is finalized
 pub const T: usize = 20; // number of transactions
 pub const BETA: f64 = 0.1; // FPCS parameter
 pub const N: usize = 20; // number of nodes
+pub const HISTORY_ROUNDS: usize = 100; // number of rounds kept in the opinion history
+pub const BENCH_TX_GRAPHS: [TxGraphType; 2] = [TxGraphType::Complete, TxGraphType::Star]; // tx graphs of the default benchmark set
@@ -0,0 +1,61 @@
+use crate::aux_types::{NodeId, TxId};
+use std::fmt;
+
+// Events emitted by the Database while running rounds:
+//
+// TxFinalized: tx was finalized by all honest nodes in round, with the given agreement rate
+// NodeFinalized: node finalized all its opinions in round
+// SafetyViolated: in round, node finalized tx as liked while its conflict was already finalized as liked
+// (the conflict keeps its final opinion)
+// RoundCompleted: round finished
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    TxFinalized { tx: TxId, round: u32, agreement_rate: f64 },
+    NodeFinalized { node: NodeId, round: u32 },
+    SafetyViolated { node: NodeId, tx: TxId, conflict: TxId, round: u32 },
+    RoundCompleted { round: u32 },
+}
+
+// Callback registered on a Database to receive its events.
+// The default handler ignores all events.
+pub struct EventHandler(Box<dyn Fn(&Event) + Send + Sync>);
+
+impl EventHandler {
+    pub fn new(handler: impl Fn(&Event) + Send + Sync + 'static) -> Self {
+        Self(Box::new(handler))
+    }
+
+    pub fn handle(&self, event: &Event) {
+        (self.0)(event)
+    }
+}
+
+impl Default for EventHandler {
+    fn default() -> Self {
+        Self::new(|_| {})
+    }
+}
+
+impl fmt::Debug for EventHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EventHandler")
+    }
+}
+
+// Handler that prints the events to stdout, as the binary does
+pub fn print_event(event: &Event) {
+    match event {
+        Event::TxFinalized { tx, agreement_rate, .. } => {
+            println!("tx {} finalized in all honest nodes", tx);
+            println!("Agreement rate: {:?}", agreement_rate);
+        },
+        Event::NodeFinalized { node, .. } => {
+            println!("node {} finalized all transactions", node);
+        },
+        Event::SafetyViolated { node, tx, conflict, .. } => {
+            println!("node {} finalized both tx {} and its conflict tx {} as liked", node, tx, conflict);
+        },
+        Event::RoundCompleted { .. } => {},
+    }
+}
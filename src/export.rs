@@ -0,0 +1,162 @@
+use crate::aux_types::NodeId;
+use crate::fpcs::{Database, NodeType};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+impl Database {
+    // Renders the conflict graph as an undirected GraphViz DOT graph: each tx is a vertex, 
+    // identified by its u32 id and labeled by its short (Display) form, and each conflict is an edge. 
+    // Conflicts are collected from the visions of all nodes and each edge is emitted once.
+    pub fn conflict_graph_dot(&self) -> String {
+        let mut txs = BTreeSet::new();
+        let mut edges = BTreeSet::new();
+        for node in self.data.values() {
+            for tx in node.vision.get_txs() {
+                txs.insert(tx);
+                for conflict in node.vision.get_conflict_set(&tx).iter() {
+                    edges.insert((tx.min(*conflict), tx.max(*conflict)));
+                }
+            }
+        }
+
+        let mut dot = String::from("graph conflicts {\n");
+        for tx in &txs {
+            writeln!(dot, "    {} [label=\"{}\"];", tx.get_u32(), tx).unwrap();
+        }
+        for (a, b) in &edges {
+            writeln!(dot, "    {} -- {};", a.get_u32(), b.get_u32()).unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    // Renders the communication graph as an undirected GraphViz DOT graph: each node is a vertex, 
+    // identified by its u32 id, labeled by its short (Display) form and colored by its type 
+    // (regular: green, faulty: gray, malicious: red, observer: blue), 
+    // and each neighborhood membership is an edge, emitted once.
+    pub fn node_graph_dot(&self) -> String {
+        let mut edges = BTreeSet::new();
+        let mut dot = String::from("graph nodes {\n");
+        for node in self.data.values() {
+            let color = match node.node_type {
+                NodeType::Regular => "green",
+                NodeType::Faulty => "gray",
+                NodeType::Malicious(_) => "red",
+                NodeType::Observer => "blue",
+            };
+            writeln!(dot, "    {} [label=\"{}\", color={}];", node.id.get_u32(), node.id, color).unwrap();
+            for neighbor in node.neighborhood.iter() {
+                edges.insert((node.id.min(*neighbor), node.id.max(*neighbor)));
+            }
+        }
+        for (a, b) in &edges {
+            writeln!(dot, "    {} -- {};", a.get_u32(), b.get_u32()).unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    // Writes the metrics history as CSV, with a header row and one row per round:
+    // round, finalized_tx_count, finalized_node_count, min_agreement_rate, query_count, elim_removed, comp_added.
+    // min_agreement_rate is left empty in rounds without unfinalized txs.
+    pub fn metrics_to_csv(&self, mut writer: impl io::Write) -> io::Result<()> {
+        writeln!(writer, "round,finalized_tx_count,finalized_node_count,min_agreement_rate,query_count,elim_removed,comp_added")?;
+        for metrics in self.metrics_history() {
+            let min_agreement_rate = metrics.min_agreement_rate()
+                .map_or(String::new(), |rate| rate.to_string());
+            writeln!(writer, "{},{},{},{},{},{},{}", metrics.round, metrics.finalized_tx_count, 
+                metrics.finalized_node_count, min_agreement_rate, metrics.query_count, 
+                metrics.elim_removed, metrics.comp_added)?;
+        }
+        Ok(())
+    }
+
+    // Reads a node graph from an adjacency list file, with one line per node: its u32 id, a colon 
+    // and the u32 ids of its neighbors, separated by whitespace (e.g. "3: 0 7 12"). 
+    // Empty lines and lines starting with '#' are skipped. Every referenced node gets an entry and the edges 
+    // are made symmetric, so a directed list is read as undirected. The nodes and their neighbors are sorted by id.
+    // The ids are taken as is, see set_node_graph (and DatabaseBuilder::sequential_ids to number the nodes 0, 1, 2, ...).
+    pub fn load_node_graph(path: &Path) -> io::Result<Vec<(NodeId, Vec<NodeId>)>> {
+        let invalid = |line_number: usize, line: &str| io::Error::new(
+            io::ErrorKind::InvalidData, 
+            format!("line {}: expected \"id: neighbor neighbor ...\", got \"{}\"", line_number + 1, line));
+        let parse_id = |line_number: usize, line: &str, id: &str| id.parse::<u32>()
+            .map(NodeId::from_u32)
+            .map_err(|_| invalid(line_number, line));
+
+        let mut adjacency = BTreeMap::<NodeId, BTreeSet<NodeId>>::new();
+        for (line_number, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (node, neighbors) = line.split_once(':').ok_or_else(|| invalid(line_number, line))?;
+            let node = parse_id(line_number, line, node.trim())?;
+            adjacency.entry(node).or_default();
+            for neighbor in neighbors.split_whitespace() {
+                let neighbor = parse_id(line_number, line, neighbor)?;
+                if neighbor != node {
+                    adjacency.entry(node).or_default().insert(neighbor);
+                    adjacency.entry(neighbor).or_default().insert(node);
+                }
+            }
+        }
+        Ok(adjacency.into_iter()
+            .map(|(node, neighbors)| (node, neighbors.into_iter().collect()))
+            .collect())
+    }
+
+    // Writes the node graph in the format read by load_node_graph, one line per node (sorted by id)
+    pub fn save_node_graph(&self, path: &Path) -> io::Result<()> {
+        let mut text = String::new();
+        for node in self.data.values() {
+            let mut neighbors = node.neighborhood.iter().map(|id| id.get_u32()).collect::<Vec<u32>>();
+            neighbors.sort();
+            let neighbors = neighbors.iter().map(|id| id.to_string()).collect::<Vec<String>>();
+            writeln!(text, "{}: {}", node.id.get_u32(), neighbors.join(" ")).unwrap();
+        }
+        fs::write(path, text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aux_types::NodeGraphType;
+    use crate::builder::DatabaseBuilder;
+
+    fn neighbors(database: &Database, id: u32) -> Vec<u32> {
+        let mut neighbors = database.data[&NodeId::from_u32(id)].neighborhood.iter()
+            .map(|id| id.get_u32())
+            .collect::<Vec<u32>>();
+        neighbors.sort();
+        neighbors
+    }
+
+    #[test]
+    fn node_graph_round_trip() {
+        let path = std::env::temp_dir().join(format!("outro_08_node_graph_{}.txt", std::process::id()));
+        let mut database = DatabaseBuilder::new()
+            .total_nodes(4)
+            .node_graph(NodeGraphType::Complete)
+            .sequential_ids(true)
+            .seed(0)
+            .build()
+            .unwrap();
+
+        // a one-directional input edge 2 -> 3 comes back in both directions
+        fs::write(&path, "# path 0 - 1 - 2 - 3\n0: 1\n1: 0 2\n2: 3\n").unwrap();
+        let adjacency = Database::load_node_graph(&path).unwrap();
+        database.set_node_graph(&adjacency).unwrap();
+        assert_eq!(neighbors(&database, 2), [1, 3]);
+        assert_eq!(neighbors(&database, 3), [2]);
+
+        database.save_node_graph(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "0: 1\n1: 0 2\n2: 1 3\n3: 2\n");
+        assert_eq!(Database::load_node_graph(&path).unwrap(), adjacency);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,7 +1,21 @@
-use crate::aux_types::{NodeId, TxId, Opinion, NodeStatus, HashedTxId, TxGlobalStatus, Neighborhood, Vision, Conflicts, TxGraphType, NodeGraphType, intersects};
-use crate::constants::{BETA, K, L};
-use std::collections::BTreeMap;
-use rand_core::{OsRng, RngCore};
+use crate::aux_types::{Asymmetry, NodeId, TxId, Opinion, NodeStatus, HashedTxId, OrderHash, TxGlobalStatus, Neighborhood, Vision, Conflicts, TxGraphType, TxGraphSpec, NodeGraphType};
+use crate::adversary::{AdversaryController, AdversaryStrategy};
+use crate::builder::{DatabaseBuilder, InitialOpinions};
+use crate::config::{BetaSchedule, FinalizationPolicy, FpcsConfig, TieRule};
+use crate::events::{Event, EventHandler};
+use crate::metrics::{BenchResult, ConsensusSummary, LatencyStats, LedgerState, RoundDelta, RoundMetrics};
+use crate::constants::HISTORY_ROUNDS;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::sync::Arc;
+use std::time::Instant;
+use std::fmt::Write;
+use rand_chacha::ChaCha12Rng;
+use rand::{Rng, SeedableRng};
+use rand::seq::SliceRandom;
+use rand_core::RngCore;
+use thiserror::Error;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
 // A node is a collection of the following information:
 //
@@ -10,83 +24,119 @@ use rand_core::{OsRng, RngCore};
 // neighborhood: a collection of known nodes, to which the node can communicate
 // status: represents is the node finalized all its opinions or not
 // node_type: malicious, faulty or honest
-
-#[derive(Debug)]
+// weight: stake of the node, used when queries are sampled proportionally to weight (1 by default)
+// query_count: number of nodes queried per round, overriding config.k if set (None by default)
+// reliability: probability that the node responds to each query (1 by default); 
+// a query that gets no response is not counted, as for faulty nodes
+// skip_prob: probability that the node skips a round, neither querying nor updating its opinions (0 by default)
+// last_changes: for each tx, the round in which the opinion of the node last changed state 
+// (first opinion, flip between like and dislike, or finalization); no entry if it never changed
+// flip_counts: for each tx, the number of times the pending opinion of the node flipped between like and dislike
+// rng: own stream of the master seed of the database (see Database::node_rng), from which its samples are drawn
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone)]
 pub struct Node {
     pub id: NodeId,
     pub vision: Vision,
     pub neighborhood: Neighborhood,
     pub status: NodeStatus,
-    pub node_type: NodeType
+    pub node_type: NodeType,
+    pub weight: u64,
+    pub query_count: Option<usize>,
+    pub reliability: f64,
+    pub skip_prob: f64,
+    pub last_changes: BTreeMap<TxId, u32>,
+    pub flip_counts: BTreeMap<TxId, u32>,
+    pub rng: ChaCha12Rng
 } 
 
 impl Node {
-    // Samples k nodes without repetition from the neighborhood
-    fn sample_from_neighborhood(&self, k: usize) -> Vec<NodeId> {
-        self.neighborhood.sample(k)
+    // Samples k nodes without repetition from the neighborhood, 
+    // proportionally to their weights if weights are given
+    fn sample_from_neighborhood(&self, k: usize, weights: Option<&BTreeMap<NodeId, u64>>, rng: &mut impl RngCore) -> Vec<NodeId> {
+        match weights {
+            Some(weights) => self.neighborhood.sample_weighted(k, weights, rng),
+            None => self.neighborhood.sample(k, rng),
+        }
     }
 
-    fn collect_and_set_new_opinion(&self, k: usize, database: &Database, random_number: u32) -> Vec<(TxId, bool)> {
-        let node_sample = self.sample_from_neighborhood(k);
+    // random_number sets the threshold of the txs, and order_random_number their order in elim and comp
+    fn collect_and_set_new_opinion(&self, node_sample: Vec<NodeId>, database: &Database, random_number: u32, order_random_number: u32) -> NewOpinions {
 
-        let mut eta = self.vision.get_txs().iter()
-            .map(|id| (*id, 0usize) )
-            .collect::<Vec<(TxId, usize)>>();
+        // final opinions never change, so only the txs that are not final are queried;
+        // the txs finalized as liked stay in the liked set through elim and comp
+        let final_likes = self.vision.get_txs().into_iter()
+            .filter(|tx| *self.vision.get_opinion_status(tx) == Opinion::Final(true))
+            .collect::<BTreeSet<TxId>>();
 
+        // for each tx, the number of likes and the number of responses received
+        let mut eta = self.vision.get_txs().iter()
+            .filter(|id| !self.vision.get_opinion_status(id).is_final())
+            .map(|id| (*id, 0usize, 0usize) )
+            .collect::<Vec<(TxId, usize, usize)>>();
+
+        // only responses count towards the threshold of a tx: faulty nodes and observers never respond, 
+        // honest nodes that do not know the tx, have no opinion on it or are not confident enough in it
+        // (see FpcsConfig::response_confidence) abstain (neither like nor dislike), 
+        // and so do malicious nodes whose strategy gives no response (see AdversaryStrategy::response)
         for queried_node in node_sample {
+            // observers never vote
+            if database.data.get(&queried_node).unwrap().is_faulty() || database.data.get(&queried_node).unwrap().is_observer(){
+                continue;
+            }
+
             if database.data.get(&queried_node).unwrap().is_honest(){
-                for (tx, likes) in &mut eta {
-                    let opinion = database.data
+                for (tx, likes, responses) in &mut eta {
+                    let response = database.data
                         .get(&queried_node).unwrap()
                         .vision
-                        .get_opinion_status(tx); 
-                    if opinion.is_like() {
-                        *likes += 1;
+                        .query_confident_opinion(tx, database.config.response_confidence); 
+                    if let Some(like) = response {
+                        *likes += like as usize;
+                        *responses += 1;
                     }
                 }
-            } else if database.data.get(&queried_node).unwrap().is_malicious(){
-                for (tx, likes) in &mut eta {
-                    let opinion = database.data
-                        .get(&self.id).unwrap()
-                        .vision
-                        .get_opinion_status(tx); 
-                    if opinion.is_like() {
-                        *likes += 1;
+            } else if let NodeType::Malicious(strategy) = &database.data.get(&queried_node).unwrap().node_type {
+                for (tx, likes, responses) in &mut eta {
+                    if let Some(like) = strategy.response(self, *tx, database.round, database) {
+                        *likes += like as usize;
+                        *responses += 1;
                     }
                 }
             }
         }
 
-        let number_of_queries = k.min(self.neighborhood.len());
-        let threshold = ((random_number as u128 * number_of_queries as u128)/(u32::max_value() as u128)) as usize;
         let mut new_auxiliary_opinion = Vec::new();
-        for (id, n) in eta {
-            if n > threshold { 
-                new_auxiliary_opinion.push((id, true)); 
-            } else {
-                new_auxiliary_opinion.push((id, false));
-            }
+        for (id, n, responses) in eta {
+            new_auxiliary_opinion.push((id, is_liked(n, responses, random_number, database.config.tie)));
         }
 
-        self.elim(&mut new_auxiliary_opinion, random_number);
-        self.comp(new_auxiliary_opinion, random_number)
+        let order_hash = database.config.order_hash;
+        let elim_removed = self.elim(&mut new_auxiliary_opinion, order_random_number, order_hash, &final_likes);
+        let (new_opinions, comp_added) = self.comp(new_auxiliary_opinion, order_random_number, order_hash, &final_likes);
+        (new_opinions, StepCounts { elim_removed, comp_added })
 
     }
 
 
     // 'elim' step of the algorithm: given an auliliary opinion, 
-    // it orders the txs accordingly to the random number sent by the oracle 
+    // it orders the txs accordingly to the random number sent by the oracle (hashed with order_hash)
     // and uses this order to eliminate transactions from the liked set until the set is independent.
-    fn elim(&self, auxiliary_opinion: &mut Vec<(TxId, bool)>, random_number: u32){
+    // final_likes (not in the auxiliary opinion) are part of the liked set and are never eliminated.
+    // Returns the number of txs eliminated.
+    fn elim(&self, auxiliary_opinion: &mut [(TxId, bool)], random_number: u32, order_hash: OrderHash, final_likes: &BTreeSet<TxId>) -> usize {
         // Sorts auliliary opinion vector by hashed TxId (largest to smallest)
         auxiliary_opinion.sort_by(move |a, b| {
             let hash_a = HashedTxId {
                 id: a.0,
-                random_number
+                random_number,
+                order_hash
             };
             let hash_b = HashedTxId {
                 id: b.0,
-                random_number
+                random_number,
+                order_hash
             };
             hash_b.cmp(&hash_a)
         });
@@ -94,9 +144,11 @@ impl Node {
         // holds the set of liked txs, for later use
         let mut liked_set = auxiliary_opinion.iter()
             .filter(|(_, b)| *b )
-            .map(|(a,_)| a)
-            .collect::<Vec<TxId>>();
+            .map(|(a,_)| *a)
+            .chain(final_likes.iter().copied())
+            .collect::<BTreeSet<TxId>>();
 
+        let mut removed = 0;
         // For each liked tx, stops liking it (and removes it from the liked_set) 
         // if it conflicts with something else in the liked_set
         // This is done in the order introduced above (sorted by hashed TxId)
@@ -104,30 +156,36 @@ impl Node {
             if *opinion {
 
                 // nodes will never try to access an unknown tx by design
-                let conflicts = self.vision.get_conflict_set(&txid); 
+                let conflicts = self.vision.get_conflict_set(txid); 
 
-                if intersects(conflicts.get(), &liked_set) {
+                if conflicts.intersects_set(&liked_set) {
                     *opinion = false;
-                    liked_set.retain(|&x| x!= *txid);
+                    liked_set.remove(txid);
+                    removed += 1;
                 }   
             }
         }
+        removed
     }
 
     // 'comp' step of the algorithm: given an auliliary opinion after the 'elim' step,
     // which means that now the liked set is independent, 
-    // it orders the txs accordingly to the random number sent by the oracle 
+    // it orders the txs accordingly to the random number sent by the oracle (hashed with order_hash)
     // and uses this order to add transactions from the unliked set until the liked set is maximal.
-    fn comp(&self, mut auxiliary_opinion: Vec<(TxId, bool)>, random_number: u32) -> Vec<(TxId, bool)> {
+    // final_likes (not in the auxiliary opinion) are part of the liked set.
+    // Also returns the number of txs added to the liked set.
+    fn comp(&self, mut auxiliary_opinion: Vec<(TxId, bool)>, random_number: u32, order_hash: OrderHash, final_likes: &BTreeSet<TxId>) -> (Vec<(TxId, bool)>, usize) {
         // Sorts auliliary opinion vector by hashed TxId (smallest to largest)
         auxiliary_opinion.sort_by(move |a, b| {
             let hash_a = HashedTxId {
                 id: a.0,
-                random_number
+                random_number,
+                order_hash
             };
             let hash_b = HashedTxId {
                 id: b.0,
-                random_number
+                random_number,
+                order_hash
             };
             hash_a.cmp(&hash_b)
         });
@@ -135,51 +193,120 @@ impl Node {
         // holds the set of liked txs, for later use
         let mut liked_set = auxiliary_opinion.iter()
             .filter(|(_, b)| *b )
-            .map(|(a,_)| a)
-            .collect::<Vec<TxId>>();
+            .map(|(a,_)| *a)
+            .chain(final_likes.iter().copied())
+            .collect::<BTreeSet<TxId>>();
 
+        let mut added = 0;
         // For each unliked tx, likes it (and adds it to the liked_set) 
         // if it does not conflict with something else in the liked_set
         // This is done in the order introduced above (sorted by hashed TxId)
         for (txid, opinion) in auxiliary_opinion.iter_mut() {
             if !*opinion {
                 // nodes will never try to access an unknown tx by design
-                let conflicts = self.vision.get_conflict_set(&txid); 
+                let conflicts = self.vision.get_conflict_set(txid); 
 
-                if !intersects(conflicts.get(), &liked_set) {
+                if !conflicts.intersects_set(&liked_set) {
                     *opinion = true;
-                    liked_set.push(*txid);
+                    liked_set.insert(*txid);
+                    added += 1;
                 }   
             }
         }
 
-        auxiliary_opinion
+        (auxiliary_opinion, added)
     }
 
-    fn update_opinions(&mut self, new_opinions: Vec<(TxId, bool)>){
+    // l is the number of rounds before a tx is finalized, unless the tx has its own in tx_l;
+    // round is recorded in last_changes for every opinion that changes state
+    fn update_opinions(&mut self, new_opinions: Vec<(TxId, bool)>, l: u32, tx_l: &BTreeMap<TxId, u32>, round: u32) -> OpinionChanges {
+        let mut changes = OpinionChanges::default();
+        // txs finalized as liked, kept up to date during the batch
+        let mut finalized_likes = self.vision.get_txs().into_iter()
+            .filter(|tx| *self.vision.get_opinion_status(tx) == Opinion::Final(true))
+            .collect::<BTreeSet<TxId>>();
         for (id, new_opinion) in new_opinions{
-            let conflicts = self.vision.get_conflict_set(&id).to_owned();
+            let l = tx_l.get(&id).copied().unwrap_or(l);
             let opinion = self.vision.get_opinion_status(&id).to_owned();
+
+            // a tx added or received after one of its conflicts was finalized as liked 
+            // is finalized as disliked, as if it had been known when the conflict was finalized
+            let conflict_finalized = !opinion.is_final() 
+                && self.vision.get_conflict_set(&id).intersects_set(&finalized_likes);
+            if conflict_finalized {
+                self.vision.set_opinion(&id, Opinion::Final(false));
+                self.last_changes.insert(id, round);
+                continue;
+            }
+
             match opinion {
-                Opinion::Pending(a, b) if a && new_opinion && b >= L-1 => { 
+                Opinion::Pending(a, b) if a && new_opinion && b >= l-1 => { 
                     self.vision.set_opinion(&id, Opinion::Final(true));
-                    for conflict in conflicts.iter() {
-                        self.vision.set_opinion(conflict, Opinion::Final(false));
+                    self.last_changes.insert(id, round);
+                    finalized_likes.insert(id);
+                    let conflicts = self.vision.get_conflict_set(&id).sorted_iter().copied().collect::<Vec<TxId>>();
+                    for conflict in &conflicts {
+                        match self.vision.try_get_opinion_status(conflict) {
+                            // a final opinion is never overwritten (a conflict already finalized as liked 
+                            // makes the tx finalized as disliked above, so this only guards against a broken invariant)
+                            Some(Opinion::Final(true)) => {
+                                changes.safety_violations.push(SafetyViolation { node: self.id, tx: id, conflict: *conflict });
+                            },
+                            Some(Opinion::Final(false)) => {},
+                            Some(_) => {
+                                self.vision.set_opinion(conflict, Opinion::Final(false));
+                                self.last_changes.insert(*conflict, round);
+                            },
+                            // conflicts that are still propagating are not known yet
+                            None => {},
+                        }
                     } 
+                    changes.finalized += 1;
                 },
                 Opinion::Pending(a, b) if a == new_opinion => { 
                     self.vision.set_opinion(&id, Opinion::Pending(a, b+1));
                 },
                 Opinion::Pending(a, _) if a != new_opinion => { 
                     self.vision.set_opinion(&id, Opinion::Pending(new_opinion, 0));
+                    self.last_changes.insert(id, round);
+                    *self.flip_counts.entry(id).or_insert(0) += 1;
+                    changes.flipped.push(id);
+                },
+                // a tx received by gossip (or any tx, for an observer) gets its first opinion from the first query round
+                Opinion::None => {
+                    self.vision.set_opinion(&id, Opinion::Pending(new_opinion, 0));
+                    self.last_changes.insert(id, round);
                 },
                 _ => {},
             }
         }
 
+        // the new opinions come out of elim and comp, so the liked set stays independent
+        // (unless two conflicting txs were finalized as liked, which is reported as a safety violation)
+        debug_assert!(self.vision.liked_set_is_independent() || self.find_safety_violation().is_some(), 
+            "{:?} likes conflicting txs", self.id);
+
         if self.vision.has_finalized() { 
             self.status = NodeStatus::Finalized; 
         }
+        changes
+    }
+
+    // First tx (ordered by id) finalized as liked with a conflict also finalized as liked, if any
+    fn find_safety_violation(&self) -> Option<SafetyViolation> {
+        for tx in self.vision.get_txs() {
+            if !matches!(self.vision.get_opinion_status(&tx), Opinion::Final(true)) { continue; }
+            for conflict in self.vision.get_conflict_set(&tx).sorted_iter() {
+                if matches!(self.vision.try_get_opinion_status(conflict), Some(Opinion::Final(true))) {
+                    return Some(SafetyViolation { node: self.id, tx, conflict: *conflict });
+                }
+            }
+        }
+        None
+    }
+
+    fn is_observer(&self) -> bool{
+        self.node_type == NodeType::Observer
     }
 
     fn is_faulty(&self) -> bool{
@@ -190,268 +317,2736 @@ impl Node {
         self.node_type == NodeType::Regular
     }
 
-    fn is_malicious(&self) -> bool{
-        self.node_type == NodeType::Malicious
-    }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug)]
 pub struct Database{
     pub data: BTreeMap<NodeId, Node>, 
     tx_set: Vec<(TxId, TxGlobalStatus)>,
-    pub node_set: Vec<(NodeId, NodeType, NodeStatus)>
+    pub node_set: Vec<(NodeId, NodeType, NodeStatus)>,
+    config: FpcsConfig,
+    node_graph_type: NodeGraphType,
+    rng: ChaCha12Rng,
+    // master seed, from which the rng of each node is derived
+    seed: u64,
+    round: u32,
+    finalization_rounds: BTreeMap<TxId, u32>,
+    metrics_history: Vec<RoundMetrics>,
+    // txs that need a number of rounds other than config.l to be finalized
+    finalization_thresholds: BTreeMap<TxId, u32>,
+    // number of queries sent by honest nodes and observers since the first round
+    total_query_count: u64,
+    // opinion changes of the honest nodes in the last HISTORY_ROUNDS rounds
+    opinion_history: VecDeque<BTreeMap<NodeId, OpinionChanges>>,
+    // if true, new nodes and txs get the next free sequential id instead of a random one
+    sequential_ids: bool,
+    // plan of the malicious nodes for the current round, if their strategy makes one
+    #[cfg_attr(feature = "serde", serde(skip))]
+    adversary_plan: Option<AdversaryController>,
+    // BETA of each round, config.beta in every round if not set
+    #[cfg_attr(feature = "serde", serde(skip))]
+    beta_schedule: Option<BetaSchedule>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    event_handler: EventHandler
+}
+
+// Errors returned when a Database cannot be generated:
+//
+// NoNodes: total_node_count (config.n) is 0
+// NoTransactions: tx_count (config.t) is 0, or a shard of TxGraphType::Union(shards) has a tx count of 0
+// NoHonestNodes: faulty_node_count + malicious_node_count + observer_node_count >= total_node_count
+// InvalidFinalizationThreshold: config.l == 0
+// InvalidBeta: config.beta outside [0, 0.5]
+// InvalidProbability: NodeGraphType::Random(p), NodeGraphType::SmallWorld { beta, .. } 
+// or TxGraphType::RandomConflict(p) outside [0, 1]
+// InvalidDegree: NodeGraphType::Regular(d) with d == 0 or d >= total_node_count,
+// or NodeGraphType::SmallWorld { k, .. } with k odd, k == 0 or k >= total_node_count
+// GridSizeMismatch: NodeGraphType::Grid { width, height } with width * height != total_node_count
+// TooFewTransactions: TxGraphType::Cycle with tx_count < 3, or TxGraphType::Wheel with tx_count < 4
+// TxCountMismatch: TxGraphType::Clusters { clusters, size } with clusters * size != tx_count,
+// TxGraphType::Bipartite { left, right } with left + right != tx_count,
+// or TxGraphType::Union(shards) whose tx counts do not add up to tx_count
+// (the other errors of a tx graph type also apply to each shard of a union, with the tx count of the shard)
+// NoLikedTxs: LikeDistributions::Concentrated(0)
+// InvalidDistributionLength: LikeDistributions::Weighted(probabilities) with probabilities.len() != tx_count
+// InvalidProbability: LikeDistributions::Weighted(probabilities) with a probability outside [0, 1]
+// InvalidProbability: DatabaseBuilder::tx_knowledge(p) outside [0, 1], or a fraction of FinalizationPolicy::Threshold
+// or FinalizationPolicy::StakeThreshold (in config.finalization_policy) outside (0, 1]
+// AsymmetricConflicts: the generated conflict graph is not symmetric
+// DuplicateTx: from_conflict_edges with a tx declared twice
+// UnknownTx: from_conflict_edges with an edge to a tx that is not declared
+// SelfConflict: from_conflict_edges with an edge from a tx to itself
+// UnknownNode: DatabaseBuilder::initial_opinions with a node that is not an honest node of the database
+// UnknownTx: DatabaseBuilder::initial_opinions with a tx that is not in the database
+// ConflictingOpinion: DatabaseBuilder::initial_opinions with two conflicting txs liked by the same node
+//
+// and when a Database cannot be changed:
+//
+// UnknownTx: add_new_tx with a conflict that is not in the database
+// UnknownNode: remove_node with a node that is not in the database
+// UnknownTx: set_finalization_threshold with a tx that is not in the database
+// InvalidFinalizationThreshold: set_finalization_threshold with l == 0
+// NoHonestNodes: remove_node with the last honest node
+// UnknownNode: set_node_opinion with a node that is not in the database
+// UnknownNode: set_node_graph with a node that is not in the database
+// UnknownTx: set_node_opinion with a tx that the node does not know
+// ConflictingOpinion: set_node_opinion (without force) with Final(true) on a tx that has a liked conflict
+
+#[derive(Debug, Error, PartialEq)]
+pub enum DatabaseError {
+    #[error("the database needs at least 1 node")]
+    NoNodes,
+    #[error("the database needs at least 1 transaction")]
+    NoTransactions,
+    #[error("you need at least 1 honest node: {faulty} faulty, {malicious} malicious and {observers} observers out of {total} nodes")]
+    NoHonestNodes {
+        total: usize,
+        faulty: usize,
+        malicious: usize,
+        observers: usize,
+    },
+    #[error("probability {0} is not in [0, 1]")]
+    InvalidProbability(f64),
+    #[error("beta {0} is not in [0, 0.5]")]
+    InvalidBeta(f64),
+    #[error("the conflict graph needs at least {minimum} transactions, got {tx_count}")]
+    TooFewTransactions {
+        tx_count: usize,
+        minimum: usize,
+    },
+    #[error("the conflict graph has {expected} transactions, but tx_count is {tx_count}")]
+    TxCountMismatch {
+        expected: usize,
+        tx_count: usize,
+    },
+    #[error("invalid degree {degree} for {total} nodes")]
+    InvalidDegree {
+        degree: usize,
+        total: usize,
+    },
+    #[error("a {width}x{height} grid does not fit {total} nodes")]
+    GridSizeMismatch {
+        width: usize,
+        height: usize,
+        total: usize,
+    },
+    #[error("the initial likes must be concentrated in at least 1 transaction")]
+    NoLikedTxs,
+    #[error("the initial distribution has {length} probabilities, but tx_count is {tx_count}")]
+    InvalidDistributionLength {
+        length: usize,
+        tx_count: usize,
+    },
+    #[error(transparent)]
+    AsymmetricConflicts(#[from] Asymmetry),
+    #[error("unknown transaction {0:?}")]
+    UnknownTx(TxId),
+    #[error("transaction {0:?} is declared more than once")]
+    DuplicateTx(TxId),
+    #[error("transaction {0:?} conflicts with itself")]
+    SelfConflict(TxId),
+    #[error("unknown node {0:?}")]
+    UnknownNode(NodeId),
+    #[error("a transaction needs at least 1 round to be finalized, got {0}")]
+    InvalidFinalizationThreshold(u32),
+    #[error("{node:?} cannot like {tx:?}, since it likes the conflicting {conflict:?}")]
+    ConflictingOpinion {
+        node: NodeId,
+        tx: TxId,
+        conflict: TxId,
+    },
+}
+
+// Two conflicting txs, tx and conflict, are both finalized as liked in the same honest node
+
+#[derive(Debug, Clone, Error, PartialEq)]
+#[error("{node:?} finalized both {tx:?} and its conflict {conflict:?} as liked")]
+pub struct SafetyViolation {
+    pub node: NodeId,
+    pub tx: TxId,
+    pub conflict: TxId,
+}
+
+// Activity of the elim and comp steps of a node (or of all nodes) in one round: 
+// the number of likes dropped by elim and the number of likes added by comp
+
+#[derive(Debug, Clone, Copy, Default)]
+struct StepCounts {
+    elim_removed: usize,
+    comp_added: usize,
+}
+
+// New opinions of a node after a query round, with the activity of its elim and comp steps
+type NewOpinions = (Vec<(TxId, bool)>, StepCounts);
+
+// Opinion changes of an honest node in one round, kept in the opinion history of the Database
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Default)]
+struct OpinionChanges {
+    // txs whose pending opinion flipped
+    flipped: Vec<TxId>,
+    // number of txs finalized as liked (their conflicts are finalized as disliked)
+    finalized: usize,
+    // conflicts that were already finalized as liked when a tx was finalized as liked;
+    // they are reported as events, not kept in the history
+    #[cfg_attr(feature = "serde", serde(skip))]
+    safety_violations: Vec<SafetyViolation>,
+}
+
+// Result of Database::run_until_final (or Database::run_until_agreement), with the number of rounds run so far:
+//
+// Converged: all honest nodes finalized (or reached the target agreement)
+// Stalled: the round cap was reached before all honest nodes finalized
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunOutcome {
+    Converged(u32),
+    Stalled(u32),
+}
+
+// Iterator over the rounds of a Database, returned by Database::rounds: each call to next runs 
+// one round and yields its metrics, until all honest nodes finalize
+
+pub struct Rounds<'a> {
+    database: &'a mut Database,
+}
+
+impl Iterator for Rounds<'_> {
+    type Item = RoundMetrics;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.database.is_final() {
+            return None;
+        }
+        self.database.run_fpcs_round();
+        self.database.metrics_history.last().cloned()
+    }
 }
 
 impl Database {
     pub fn generate_new (
-        total_node_count: usize, 
+        config: &FpcsConfig,
+        faulty_node_count: usize, 
+        malicious_node_count: usize, 
+        node_graph_type: NodeGraphType, 
+        tx_graph_type: TxGraphType,
+        initial_distribution: LikeDistributions ) -> Result<Database, DatabaseError> {
+        DatabaseBuilder::new()
+            .config(config)
+            .faulty(faulty_node_count)
+            .malicious(malicious_node_count)
+            .node_graph(node_graph_type)
+            .tx_graph(tx_graph_type)
+            .distribution(initial_distribution)
+            .build()
+    }
+
+    // Same as generate_new, but the conflict graph is given explicitly: txs are the transactions
+    // (so the tx count is txs.len()) and each edge (a, b) makes a and b conflict with each other
+    pub fn from_conflict_edges (
+        config: &FpcsConfig,
+        faulty_node_count: usize, 
+        malicious_node_count: usize, 
+        node_graph_type: NodeGraphType, 
+        txs: &[TxId],
+        edges: &[(TxId, TxId)],
+        initial_distribution: LikeDistributions ) -> Result<Database, DatabaseError> {
+        DatabaseBuilder::new()
+            .config(config)
+            .faulty(faulty_node_count)
+            .malicious(malicious_node_count)
+            .node_graph(node_graph_type)
+            .conflict_edges(txs, edges)
+            .distribution(initial_distribution)
+            .build()
+    }
+
+    // Builds a network of honest nodes only, numbered 0, 1, 2, ... (with sequential ids), over the conflict graph 
+    // given by txs and edges (see from_conflict_edges), where each node starts with exactly the given opinions 
+    // instead of a random distribution (see DatabaseBuilder::initial_opinions)
+    pub fn from_initial_opinions (
+        config: &FpcsConfig,
+        node_graph_type: NodeGraphType,
+        txs: &[TxId],
+        edges: &[(TxId, TxId)],
+        opinions: InitialOpinions ) -> Result<Database, DatabaseError> {
+        DatabaseBuilder::new()
+            .config(config)
+            .node_graph(node_graph_type)
+            .conflict_edges(txs, edges)
+            .sequential_ids(true)
+            .initial_opinions(opinions)
+            .build()
+    }
+
+    // Single entry point for benchmarks (benches/fpcs.rs runs it over BENCH_TX_GRAPHS): generates a seeded database 
+    // of honest nodes only, with a complete node graph and the given tx graph, and runs it until all nodes finalize 
+    // or max_rounds rounds are run
+    pub fn run_benchmark(config: &FpcsConfig, tx_graph_type: TxGraphType, seed: u64, max_rounds: usize) -> Result<BenchResult, DatabaseError> {
+        let mut database = DatabaseBuilder::new()
+            .config(config)
+            .tx_graph(tx_graph_type)
+            .seed(seed)
+            .build()?;
+        let start = Instant::now();
+        let outcome = database.run_until_final(max_rounds);
+        Ok(BenchResult {
+            outcome,
+            total_query_count: database.total_query_count,
+            elapsed: start.elapsed(),
+        })
+    }
+
+    // Same as generate_new, but all the randomness of the simulation (ids, samples and 
+    // round random numbers) comes from a single rng seeded with seed, 
+    // so two runs with the same seed and parameters are identical.
+    pub fn generate_new_seeded (
+        config: &FpcsConfig,
         faulty_node_count: usize, 
         malicious_node_count: usize, 
         node_graph_type: NodeGraphType, 
-        tx_count: usize, 
         tx_graph_type: TxGraphType,
-        initial_distribution: LikeDistributions ) -> Database {
-        if malicious_node_count + faulty_node_count >= total_node_count {
-            panic!("You need at least 1 honest node");
+        initial_distribution: LikeDistributions,
+        seed: u64 ) -> Result<Database, DatabaseError> {
+        DatabaseBuilder::new()
+            .config(config)
+            .faulty(faulty_node_count)
+            .malicious(malicious_node_count)
+            .node_graph(node_graph_type)
+            .tx_graph(tx_graph_type)
+            .distribution(initial_distribution)
+            .seed(seed)
+            .build()
+    }
+
+    // Generates a new database from all the parameters collected by a DatabaseBuilder
+    pub(crate) fn generate_from(builder: DatabaseBuilder) -> Result<Database, DatabaseError> {
+        let DatabaseBuilder {
+            config,
+            faulty_node_count,
+            malicious_node_count,
+            observer_node_count,
+            node_graph_type,
+            tx_graph_type,
+            conflict_edges,
+            initial_distribution,
+            mut malicious_strategy,
+            seed,
+            sequential_ids,
+            initial_confidence,
+            tx_knowledge,
+            initial_opinions,
+        } = builder;
+        let seed = seed.unwrap_or_else(rand::random);
+        let mut rng = ChaCha12Rng::seed_from_u64(seed);
+        let total_node_count = config.n;
+        let tx_count = config.t;
+        if total_node_count == 0 {
+            return Err(DatabaseError::NoNodes);
+        }
+        if tx_count == 0 {
+            return Err(DatabaseError::NoTransactions);
+        }
+        if malicious_node_count + faulty_node_count + observer_node_count >= total_node_count {
+            return Err(DatabaseError::NoHonestNodes {
+                total: total_node_count,
+                faulty: faulty_node_count,
+                malicious: malicious_node_count,
+                observers: observer_node_count,
+            });
+        }
+        if config.l == 0 {
+            return Err(DatabaseError::InvalidFinalizationThreshold(config.l));
+        }
+        if !(0.0..=0.5).contains(&config.beta) {
+            return Err(DatabaseError::InvalidBeta(config.beta));
+        }
+        match node_graph_type {
+            NodeGraphType::Random(p) if !(0.0..=1.0).contains(&p) => {
+                return Err(DatabaseError::InvalidProbability(p));
+            },
+            NodeGraphType::Regular(degree) if degree == 0 || degree >= total_node_count => {
+                return Err(DatabaseError::InvalidDegree { degree, total: total_node_count });
+            },
+            NodeGraphType::SmallWorld { k, .. } if k == 0 || k % 2 == 1 || k >= total_node_count => {
+                return Err(DatabaseError::InvalidDegree { degree: k, total: total_node_count });
+            },
+            NodeGraphType::SmallWorld { beta, .. } if !(0.0..=1.0).contains(&beta) => {
+                return Err(DatabaseError::InvalidProbability(beta));
+            },
+            NodeGraphType::Grid { width, height } if width * height != total_node_count => {
+                return Err(DatabaseError::GridSizeMismatch { width, height, total: total_node_count });
+            },
+            _ => {},
+        }
+        if conflict_edges.is_none() {
+            validate_tx_graph(&tx_graph_type, tx_count)?;
+        }
+        if !(0.0..=1.0).contains(&tx_knowledge) {
+            return Err(DatabaseError::InvalidProbability(tx_knowledge));
+        }
+        if let FinalizationPolicy::Threshold(fraction) | FinalizationPolicy::StakeThreshold(fraction) = config.finalization_policy {
+            // a fraction of 0 would call a tx globally final before any honest node finalized it
+            if !(fraction > 0.0 && fraction <= 1.0) {
+                return Err(DatabaseError::InvalidProbability(fraction));
+            }
+        }
+        if let LikeDistributions::Concentrated(0) = initial_distribution {
+            return Err(DatabaseError::NoLikedTxs);
+        }
+        if let LikeDistributions::Weighted(probabilities) = &initial_distribution {
+            if probabilities.len() != tx_count {
+                return Err(DatabaseError::InvalidDistributionLength { length: probabilities.len(), tx_count });
+            }
+            if let Some(p) = probabilities.iter().find(|p| !(0.0..=1.0).contains(*p)) {
+                return Err(DatabaseError::InvalidProbability(*p));
+            }
         }
         
-        let (tx_set, common_preliminary_vision) = 
-            match tx_graph_type {
-                TxGraphType::Complete => generate_complete_conflict_graph(tx_count),
-                TxGraphType::Star => generate_star_conflict_graph(tx_count)
-            };
+        let (tx_set, common_preliminary_vision) = match conflict_edges {
+            Some((txs, edges)) => generate_conflict_graph_from_edges(&txs, &edges)?,
+            None => {
+                let (tx_set, vision) = generate_conflict_graph(&tx_graph_type, tx_count, &mut rng);
+                if sequential_ids {
+                    let labels = tx_set.iter().enumerate()
+                        .map(|(index, (tx, _))| (*tx, TxId::from_u32(index as u32)))
+                        .collect::<BTreeMap<TxId, TxId>>();
+                    let tx_set = tx_set.into_iter()
+                        .map(|(tx, status)| (labels[&tx], status))
+                        .collect();
+                    (tx_set, vision.relabel(&labels))
+                } else {
+                    (tx_set, vision)
+                }
+            },
+        };
+        common_preliminary_vision.validate_symmetric_conflicts()?;
 
         let mut database = Database{
             data: BTreeMap::new(),
             tx_set,
-            node_set: Vec::new()
+            node_set: Vec::new(),
+            config,
+            node_graph_type,
+            rng,
+            seed,
+            round: 0,
+            finalization_rounds: BTreeMap::new(),
+            metrics_history: Vec::new(),
+            finalization_thresholds: BTreeMap::new(),
+            total_query_count: 0,
+            opinion_history: VecDeque::new(),
+            adversary_plan: None,
+            beta_schedule: None,
+            sequential_ids,
+            event_handler: EventHandler::default()
         };
 
-        let honest_node_count = total_node_count - faulty_node_count - malicious_node_count;
+        let honest_node_count = total_node_count - faulty_node_count - malicious_node_count - observer_node_count;
         for _ in 0..honest_node_count {
-            database.add_new_node(&common_preliminary_vision, node_graph_type, NodeType::Regular);
+            database.add_new_node(&common_preliminary_vision, NodeType::Regular);
         }
         
         for _ in 0..faulty_node_count {
-            database.add_new_node(&common_preliminary_vision, node_graph_type, NodeType::Faulty);
+            database.add_new_node(&common_preliminary_vision, NodeType::Faulty);
         }        
         
+        malicious_strategy.setup(&common_preliminary_vision, &mut database.rng);
+        let malicious_strategy: Arc<dyn AdversaryStrategy> = Arc::from(malicious_strategy);
         for _ in 0..malicious_node_count {
-            database.add_new_node(&common_preliminary_vision, node_graph_type, NodeType::Malicious);
+            database.add_new_node(&common_preliminary_vision, NodeType::Malicious(malicious_strategy.clone()));
         }
 
-        let liked_tx_count = match initial_distribution {
-            LikeDistributions::Equal => tx_count,
-            LikeDistributions::Concentrated(n) => n
-        };
-
-        let n = honest_node_count/liked_tx_count;
-        let mut likes = vec![n; liked_tx_count];
-        let remaining_likes = honest_node_count - n*liked_tx_count;
-        for i in 0..remaining_likes {
-            likes[i] += 1;
+        for _ in 0..observer_node_count {
+            database.add_new_node(&common_preliminary_vision, NodeType::Observer);
         }
 
-        let like_proportions = database.tx_set.iter()
-            .map(|(a,_)|*a)
-            .zip(likes)
-            .collect::<Vec<(TxId, usize)>>();
+        database.build_node_graph(node_graph_type);
+
+        if tx_knowledge < 1.0 {
+            for node in database.data.values_mut().filter(|node| node.is_honest()) {
+                node.vision.retain(|_| database.rng.gen_bool(tx_knowledge));
+            }
+        }
 
-        database.initialize_opinions(like_proportions);
+        match initial_opinions {
+            Some(opinions) => database.set_initial_opinions(opinions)?,
+            None => database.initialize_opinions(initial_distribution, initial_confidence),
+        }
 
-        database
+        Ok(database)
     }
 
 
-    fn initialize_opinions(&mut self, like_proportions: Vec<(TxId, usize)>){
-        let expanded_like_proportions = like_proportions.into_iter()
-            .map(|(id, size)| vec![id; size])
-            .flatten()
-            .collect::<Vec<TxId>>();
+    // Sets the initial opinions of the honest nodes according to initial_distribution, 
+    // then sets the remaining opinions greedily, liking each tx that does not conflict with a liked one.
+    // All the opinions start with initial_confidence (at most L-1) consecutive agreements
+    fn initialize_opinions(&mut self, initial_distribution: LikeDistributions, initial_confidence: u32){
+        let honest_node_count = self.data.values().filter(|node| node.is_honest()).count();
+        match initial_distribution {
+            LikeDistributions::Equal | LikeDistributions::Concentrated(_) => {
+                let liked_tx_count = match initial_distribution {
+                    LikeDistributions::Concentrated(n) => n,
+                    _ => self.tx_set.len(),
+                };
+
+                let n = honest_node_count/liked_tx_count;
+                let mut likes = vec![n; liked_tx_count];
+                let remaining_likes = honest_node_count - n*liked_tx_count;
+                for like in likes.iter_mut().take(remaining_likes) {
+                    *like += 1;
+                }
 
-        self.data
-            .values_mut()
-            .filter(|node| node.is_honest() )
-            .zip(expanded_like_proportions)
-            .for_each( |(node, id)| {
-                node.vision.set_opinion(&id, Opinion::Pending(true, 0)); 
-            });
+                let expanded_like_proportions = self.tx_set.iter()
+                    .map(|(a,_)|*a)
+                    .zip(likes)
+                    .flat_map(|(id, size)| vec![id; size])
+                    .collect::<Vec<TxId>>();
+
+                self.data
+                    .values_mut()
+                    .filter(|node| node.is_honest() )
+                    .zip(expanded_like_proportions)
+                    // with partial knowledge, a node may not know the tx it is given
+                    .filter(|(node, id)| node.vision.contains(id))
+                    .for_each( |(node, id)| {
+                        node.vision.set_opinion(&id, Opinion::Pending(true, 0)); 
+                    });
+            },
+            LikeDistributions::UniformIndependent => {
+                // the greedy pass over a random order of the txs gives a random maximal independent set
+                for node in self.data.values_mut().filter(|node| node.is_honest() ) {
+                    let mut txs = node.vision.get_txs();
+                    txs.shuffle(&mut self.rng);
+                    let mut liked_set = BTreeSet::new();
+                    for txid in txs {
+                        let like = !node.vision.get_conflict_set(&txid).intersects_set(&liked_set);
+                        if like {
+                            liked_set.insert(txid);
+                        }
+                        node.vision.set_opinion(&txid, Opinion::Pending(like, 0));
+                    }
+                }
+            },
+            LikeDistributions::Weighted(probabilities) => {
+                for node in self.data.values_mut().filter(|node| node.is_honest() ) {
+                    let mut sampled_likes = self.tx_set.iter()
+                        .zip(&probabilities)
+                        .filter(|(_, p)| self.rng.gen_bool(**p))
+                        .map(|((txid, _), _)| *txid)
+                        .filter(|txid| node.vision.contains(txid))
+                        .collect::<Vec<TxId>>();
+
+                    // repairs the sampled likes in a random order, so that the liked set is independent
+                    sampled_likes.shuffle(&mut self.rng);
+                    let mut liked_set = BTreeSet::new();
+                    for txid in sampled_likes {
+                        if !node.vision.get_conflict_set(&txid).intersects_set(&liked_set) {
+                            liked_set.insert(txid);
+                        }
+                    }
+                    for txid in node.vision.get_txs() {
+                        node.vision.set_opinion(&txid, Opinion::Pending(liked_set.contains(&txid), 0));
+                    }
+                }
+            },
+        }
 
         for node in self.data.values_mut().filter(|node| node.is_honest() ) {
-            let liked_set = node.vision.get_txs();
-            let mut liked_set = liked_set.into_iter().filter(|tx| node.vision.get_opinion(*tx)).collect::<Vec<TxId>>();
+            let mut liked_set = node.vision.current_liked_set().into_iter().collect::<BTreeSet<TxId>>();
 
             let unset_opinions = node.vision.get_txs().iter()
                 .filter( |id| node.vision.get_opinion_status(id).is_none() )
                 .collect::<Vec<TxId>>();
             for txid in unset_opinions {
                 let conflicts = node.vision.get_conflict_set(&txid); 
-                if !intersects(conflicts.get(), &liked_set) {
+                if !conflicts.intersects_set(&liked_set) {
                     node.vision.set_opinion(&txid, Opinion::Pending(true, 0));
-                    liked_set.push(txid);
+                    liked_set.insert(txid);
                 } else {
                     node.vision.set_opinion(&txid, Opinion::Pending(false, 0));
                 }
             }
 
         }
+
+        let initial_confidence = initial_confidence.min(self.config.l.saturating_sub(1));
+        if initial_confidence > 0 {
+            for node in self.data.values_mut().filter(|node| node.is_honest() ) {
+                for txid in node.vision.get_txs() {
+                    let like = node.vision.get_opinion_status(&txid).is_like();
+                    node.vision.set_opinion(&txid, Opinion::Pending(like, initial_confidence));
+                }
+            }
+        }
     }
 
 
 
-    fn _add_new_tx(&mut self, _tx_graph_type: TxGraphType){}
+    // Sets the given opinions of the honest nodes (see DatabaseBuilder::initial_opinions),
+    // then checks that the liked set of each node is independent
+    fn set_initial_opinions(&mut self, opinions: InitialOpinions) -> Result<(), DatabaseError> {
+        for (node_id, node_opinions) in opinions {
+            let node = self.data.get_mut(&node_id)
+                .filter(|node| node.is_honest())
+                .ok_or(DatabaseError::UnknownNode(node_id))?;
+            for (tx, opinion) in node_opinions {
+                if !node.vision.contains(&tx) {
+                    return Err(DatabaseError::UnknownTx(tx));
+                }
+                node.vision.set_opinion(&tx, opinion);
+            }
 
-    fn add_new_node(&mut self, vision: &Vision, _node_graph_type: NodeGraphType, node_type: NodeType){
-        let new_node_id = NodeId::generate();
-        let node_id_set = self.node_set.iter()
-            .map(|(id, _, _)| *id)
-            .collect::<Vec<NodeId>>();
-        let neighborhood = Neighborhood::set_new(&node_id_set);
-        for node_id in &node_id_set {
-            self.data.get_mut(node_id).unwrap().neighborhood.add(new_node_id);
+            let liked_set = node.vision.current_liked_set();
+            for tx in &liked_set {
+                let liked_conflict = node.vision.get_conflict_set(tx).sorted_iter()
+                    .find(|conflict| node.vision.query_opinion(conflict) == Some(true));
+                if let Some(conflict) = liked_conflict {
+                    return Err(DatabaseError::ConflictingOpinion { node: node_id, tx: *tx, conflict: *conflict });
+                }
+            }
+
+            if node.vision.has_finalized() {
+                node.status = NodeStatus::Finalized;
+                if let Some((_, _, status)) = self.node_set.iter_mut().find(|(id, _, _)| *id == node_id) {
+                    *status = NodeStatus::Finalized;
+                }
+            }
         }
-        self.data.insert(new_node_id, Node{ id: new_node_id, vision: vision.clone() , neighborhood, status: NodeStatus::NotFinalized, node_type: node_type.clone() });
-        self.node_set.push((new_node_id, node_type, NodeStatus::NotFinalized));
+        Ok(())
     }
 
-    pub fn run_fpcs_round(&mut self){
+    // Adds a new tx, conflicting with the txs in conflicts_with, to the vision of every node, 
+    // and returns its id. Honest nodes initialize their opinion on it with the same greedy rule 
+    // as initialize_opinions: they like it if they do not like any of its conflicts 
+    // (including conflicts already finalized as liked). 
+    // Honest nodes that had finalized go back to not finalized, since the new tx is pending.
+    pub fn add_new_tx(&mut self, conflicts_with: &[TxId]) -> Result<TxId, DatabaseError> {
+        let node_ids = self.data.keys().copied().collect::<Vec<NodeId>>();
+        self.introduce_tx(&node_ids, conflicts_with)
+    }
 
-        let random_interval_length = 1.0 - 2.0*BETA;
-        let random_number = OsRng.next_u32();
-        let random_number = (random_number as f64 * random_interval_length + u32::max_value() as f64 * BETA).floor() as u32;
-        let honest_nodes = self.node_set.iter()
-            .filter(|(_, node_type, _)| *node_type == NodeType::Regular)
-            .map(|(id,_,_)| id)
-            .collect::<Vec<NodeId>>();
-        let unfinalized_honest_nodes = self.node_set.iter()
-            .filter(|(_, node_type, status)| !status.finalized()&& *node_type == NodeType::Regular)
-            .map(|(id,_,_)| id)
-            .collect::<Vec<NodeId>>();
+    // Same as add_new_tx, but the new tx is only added to the vision of origin,
+    // and reaches the other nodes through propagate_round
+    pub fn add_new_tx_at(&mut self, origin: NodeId, conflicts_with: &[TxId]) -> Result<TxId, DatabaseError> {
+        if !self.data.contains_key(&origin) {
+            return Err(DatabaseError::UnknownNode(origin));
+        }
+        self.introduce_tx(&[origin], conflicts_with)
+    }
 
-        for node_id in &unfinalized_honest_nodes {
-            let new_opinions = self.data
-                .get(node_id).unwrap()
-                .collect_and_set_new_opinion(K, self, random_number);
-            let node = self.data.get_mut(node_id).unwrap();
-            node.update_opinions(new_opinions);
+    fn introduce_tx(&mut self, node_ids: &[NodeId], conflicts_with: &[TxId]) -> Result<TxId, DatabaseError> {
+        if let Some(tx) = conflicts_with.iter().find(|tx| !self.tx_set.iter().any(|(id, _)| id == *tx)) {
+            return Err(DatabaseError::UnknownTx(*tx));
         }
 
-        for (txid, status) in self.tx_set.iter_mut()
-            .filter(|(_,status)| !status.finalized()) {
-            
-            let mut new_status = TxGlobalStatus::Finalized;
-            for node_id in &honest_nodes {
-                let opinion = self.data.get(node_id).unwrap().vision.get_opinion_status(txid); 
-                if !opinion.is_final() {
-                    new_status = TxGlobalStatus::NotFinalized; 
-                    break; 
-                }
+        let new_tx = if self.sequential_ids {
+            let next = self.tx_set.iter().map(|(id, _)| id.get_u32() + 1).max().unwrap_or(0);
+            TxId::from_u32(next)
+        } else {
+            let mut new_tx = TxId::generate(&mut self.rng);
+            while self.tx_set.iter().any(|(id, _)| *id == new_tx) {
+                new_tx = TxId::generate(&mut self.rng);
             }
-            *status = new_status;
+            new_tx
+        };
+
+        for node_id in node_ids {
+            self.insert_tx_in_node(node_id, new_tx, conflicts_with, true);
+        }
+        self.tx_set.push((new_tx, TxGlobalStatus::NotFinalized));
 
-            if new_status == TxGlobalStatus::Finalized {
-                println!("{:?} finalized in all honest nodes", txid );
-                let likes = self.data
-                    .values()
-                    .map(|node| node.vision.get_opinion(*txid))
-                    .filter(|like| *like)
-                    .count(); 
+        Ok(new_tx)
+    }
 
-                let agreement_rate = (likes.max(honest_nodes.len()-likes) as f64)/(honest_nodes.len() as f64);
-                println!("Agreement rate: {:?}", agreement_rate );
+    // Adds tx, with the given conflicts, to the vision of a node. If initialize is set, an honest node 
+    // likes tx if it does not like any of its conflicts; otherwise, it has no opinion on tx until its next query round.
+    // Either way, an honest node (or an observer) is no longer finalized.
+    fn insert_tx_in_node(&mut self, node_id: &NodeId, tx: TxId, conflicts_with: &[TxId], initialize: bool) {
+        let node = self.data.get_mut(node_id).unwrap();
+        let opinion = if !node.is_honest() || !initialize {
+            Opinion::None
+        } else if conflicts_with.iter().any(|tx| node.vision.query_opinion(tx) == Some(true)) {
+            Opinion::Pending(false, 0)
+        } else {
+            Opinion::Pending(true, 0)
+        };
+        node.vision.add_tx(tx, conflicts_with, opinion);
+        if node.is_honest() || node.is_observer() {
+            node.status = NodeStatus::NotFinalized;
+            if let Some((_, _, status)) = self.node_set.iter_mut().find(|(id, _, _)| id == node_id) {
+                *status = NodeStatus::NotFinalized;
             }
         }
+    }
 
-        for (id, node_type , status) in self.node_set.iter_mut() {
-            if *node_type == NodeType::Regular {
-                let old_status = status.clone();
-                *status = self.data.get(id).unwrap().status;
-                if !old_status.finalized() && status.finalized() {
-                    println!("{:?} finalized all transactions", id);
+    // Gossip of the txs that are not known by every node yet: each node that is not faulty sends every tx 
+    // it knows to a random sample of up to k (config.k) of its neighbors, which add the txs they did not know 
+    // to their vision, with their conflicts and no opinion. What is sent depends only on the state 
+    // at the beginning of the call.
+    pub fn propagate_round(&mut self) {
+        let mut transfers = Vec::new();
+        for node in self.data.values().filter(|node| !node.is_faulty()) {
+            let txs = node.vision.get_txs();
+            for neighbor in node.neighborhood.sample(self.config.k, &mut self.rng) {
+                let neighbor_vision = &self.data[&neighbor].vision;
+                for tx in txs.iter().filter(|tx| !neighbor_vision.contains(tx)) {
+                    let conflicts = node.vision.get_conflict_set(tx).sorted_iter().copied().collect::<Vec<TxId>>();
+                    transfers.push((neighbor, *tx, conflicts));
                 }
             }
         }
 
+        for (receiver, tx, conflicts) in transfers {
+            if !self.data[&receiver].vision.contains(&tx) {
+                self.insert_tx_in_node(&receiver, tx, &conflicts, false);
+            }
+        }
     }
 
-    pub fn is_final(&self) -> bool {
-        for (_, node_type, status) in &self.node_set {
-            if !status.finalized() && *node_type == NodeType::Regular { return false; }
-        } 
-        true
+    // Makes tx need l consecutive rounds of agreement to be finalized, instead of config.l
+    // (e.g. more confirmations for high-value txs)
+    pub fn set_finalization_threshold(&mut self, tx: TxId, l: u32) -> Result<(), DatabaseError> {
+        if !self.tx_set.iter().any(|(id, _)| *id == tx) {
+            return Err(DatabaseError::UnknownTx(tx));
+        }
+        if l == 0 {
+            return Err(DatabaseError::InvalidFinalizationThreshold(l));
+        }
+        self.finalization_thresholds.insert(tx, l);
+        Ok(())
     }
 
-    pub fn print_results(&self) {
-        for node in self.data.values(){
-            if node.is_honest(){
-                let txs = node.vision.get_txs();
-                let opinion = txs.iter()
-                    .map(|tx| (*tx, node.vision.get_opinion_status(tx).clone() ) )
-                    .collect::<Vec<(TxId, Opinion)>>(); 
-                println!("{:?}: Status {:?}", node.id, node.status);
-                println!("Current vision: {:?}", opinion);
+    // Number of consecutive rounds of agreement tx needs to be finalized
+    pub fn finalization_threshold(&self, tx: TxId) -> u32 {
+        self.finalization_thresholds.get(&tx).copied().unwrap_or(self.config.l)
+    }
+
+    // Sets the opinion of node on tx directly, bypassing the protocol, to set up a scenario before running rounds.
+    // Finalizing a tx as liked while the node likes one of its conflicts is rejected, unless force is set.
+    // The status of the node is updated: finalized if all its opinions are final, not finalized otherwise.
+    pub fn set_node_opinion(&mut self, node: NodeId, tx: TxId, opinion: Opinion, force: bool) -> Result<(), DatabaseError> {
+        let node = self.data.get_mut(&node).ok_or(DatabaseError::UnknownNode(node))?;
+        if !node.vision.contains(&tx) {
+            return Err(DatabaseError::UnknownTx(tx));
+        }
+        if opinion == Opinion::Final(true) && !force {
+            let liked_conflict = node.vision.get_conflict_set(&tx).sorted_iter()
+                .find(|conflict| node.vision.query_opinion(conflict) == Some(true));
+            if let Some(conflict) = liked_conflict {
+                return Err(DatabaseError::ConflictingOpinion { node: node.id, tx, conflict: *conflict });
             }
         }
+        node.vision.set_opinion(&tx, opinion);
+
+        node.status = if node.vision.has_finalized() { NodeStatus::Finalized } else { NodeStatus::NotFinalized };
+        if let Some((_, _, status)) = self.node_set.iter_mut().find(|(id, _, _)| *id == node.id) {
+            *status = node.status;
+        }
+        Ok(())
     }
 
-}
+    // Fraction of the honest nodes that know each tx
+    pub fn tx_coverage(&self) -> BTreeMap<TxId, f64> {
+        let honest_nodes = self.data.values()
+            .filter(|node| node.is_honest())
+            .collect::<Vec<&Node>>();
+        self.tx_set.iter()
+            .map(|(tx, _)| {
+                let known = honest_nodes.iter().filter(|node| node.vision.contains(tx)).count();
+                (*tx, known as f64 / honest_nodes.len() as f64)
+            })
+            .collect()
+    }
 
+    // Union of the conflict sets of tx in the visions of the honest nodes that know it
+    // (they may differ, e.g. while txs propagate); empty if no honest node knows tx
+    pub fn global_conflicts(&self, tx: TxId) -> BTreeSet<TxId> {
+        self.data.values()
+            .filter(|node| node.is_honest() && node.vision.contains(&tx))
+            .flat_map(|node| node.vision.get_conflict_set(&tx).iter().copied())
+            .collect()
+    }
 
+    // Conflicts of each tx, merged over the visions of all nodes (they differ while txs propagate),
+    // with no opinions
+    fn common_vision(&self) -> Vision {
+        let mut conflicts = BTreeMap::<TxId, BTreeSet<TxId>>::new();
+        for node in self.data.values() {
+            for tx in node.vision.get_txs() {
+                conflicts.entry(tx).or_default().extend(node.vision.get_conflict_set(&tx).iter());
+            }
+        }
+        let common_vision = conflicts.into_iter()
+            .map(|(tx, conflict_set)| (tx, (Conflicts::new_from(&conflict_set.into_iter().collect::<Vec<TxId>>()), Opinion::None)))
+            .collect::<BTreeMap<TxId, (Conflicts, Opinion)>>();
+        Vision::new_from(&common_vision)
+    }
 
-fn generate_complete_conflict_graph(tx_count: usize) -> (Vec<(TxId, TxGlobalStatus)>, Vision) {
+    // Adds a node to the database, with an empty neighborhood.
+    // The neighborhoods are built afterwards by build_node_graph.
+    fn add_new_node(&mut self, vision: &Vision, node_type: NodeType) -> NodeId {
+        let new_node_id = if self.sequential_ids {
+            NodeId::from_u32(self.data.keys().last().map_or(0, |id| id.get_u32() + 1))
+        } else {
+            Database::draw_node_id(&mut self.rng, &self.data)
+        };
+        let neighborhood = Neighborhood::new();
+        self.data.insert(new_node_id, Node{ id: new_node_id, vision: vision.clone() , neighborhood, status: NodeStatus::NotFinalized, node_type: node_type.clone(), weight: 1, query_count: None, reliability: 1.0, skip_prob: 0.0, last_changes: BTreeMap::new(), flip_counts: BTreeMap::new(), rng: Database::node_rng(self.seed, new_node_id) });
+        self.node_set.push((new_node_id, node_type, NodeStatus::NotFinalized));
+        new_node_id
+    }
 
-    let tx_id_set = (0..tx_count).map(|_| TxId::generate() ).collect::<Vec<TxId>>();
-    let tx_set = tx_id_set.clone().into_iter()
-        .zip( vec![TxGlobalStatus::NotFinalized; tx_count] )
-        .collect::<Vec<(TxId, TxGlobalStatus)>>();
+    // Random id that is not in use by a node of data: a colliding id is drawn again, so that no node is overwritten
+    fn draw_node_id(rng: &mut impl RngCore, data: &BTreeMap<NodeId, Node>) -> NodeId {
+        let mut node_id = NodeId::generate(rng);
+        while data.contains_key(&node_id) {
+            node_id = NodeId::generate(rng);
+        }
+        node_id
+    }
 
-    let mut common_preliminary_vision = BTreeMap::new();
-    for i in 0..tx_count {
-        let mut conflicts = tx_id_set.clone();
-        let element = conflicts.remove(i);
-        let conflict_set = Conflicts::new_from(&conflicts);
-        common_preliminary_vision.insert(element, (conflict_set, Opinion::None));
+    // Rng of a node: the stream id + 1 of the master seed, so that the streams of the nodes never overlap 
+    // with each other (add_new_node never gives an id that is in use) or with the rng of the database (stream 0)
+    fn node_rng(seed: u64, node_id: NodeId) -> ChaCha12Rng {
+        let mut rng = ChaCha12Rng::seed_from_u64(seed);
+        rng.set_stream(node_id.get_u32() as u64 + 1);
+        rng
     }
 
-    let common_preliminary_vision = Vision::new_from(&common_preliminary_vision);
+    // Adds a node of the given type to a running simulation and returns its id.
+    // The node is wired into the existing topology according to the node graph type of the database
+    // (with NodeGraphType::SmallWorld { k, .. }, it joins as in NodeGraphType::Regular(k), 
+    // and with NodeGraphType::Grid, as in NodeGraphType::Regular(4), since it has no place in the grid).
+    // It gets the common vision; if it is honest, its opinions are initialized by initialize_joining_opinions.
+    pub fn join_node(&mut self, node_type: NodeType) -> NodeId {
+        let mut vision = self.common_vision();
+        if node_type == NodeType::Regular {
+            self.initialize_joining_opinions(&mut vision);
+        }
 
-    (tx_set, common_preliminary_vision)
-}
+        let other_node_ids = self.node_set.iter()
+            .map(|(id, _, _)| *id)
+            .collect::<Vec<NodeId>>();
+        let node_id = self.add_new_node(&vision, node_type);
+        match self.node_graph_type {
+            NodeGraphType::Complete => {
+                for neighbor in &other_node_ids {
+                    self.link_nodes(&node_id, neighbor);
+                }
+            },
+            NodeGraphType::Random(p) => {
+                let mut linked = false;
+                for neighbor in &other_node_ids {
+                    if self.rng.gen_bool(p) {
+                        self.link_nodes(&node_id, neighbor);
+                        linked = true;
+                    }
+                }
+                // for low probabilities, guarantees that the node is not isolated
+                if let (false, Some(neighbor)) = (linked, other_node_ids.choose(&mut self.rng)) {
+                    self.link_nodes(&node_id, neighbor);
+                }
+            },
+            NodeGraphType::Regular(degree) => self.link_joining_node(&node_id, degree),
+            NodeGraphType::SmallWorld { k, .. } => self.link_joining_node(&node_id, k),
+            NodeGraphType::Grid { .. } => self.link_joining_node(&node_id, 4),
+        }
+        node_id
+    }
 
-fn generate_star_conflict_graph(tx_count: usize) -> (Vec<(TxId, TxGlobalStatus)>, Vision) {
+    // Removes a node from a running simulation (e.g. a crash), together with all the edges to it.
+    // Neighbors left with less than k neighbors keep sampling from what is left of their neighborhood.
+    pub fn remove_node(&mut self, id: NodeId) -> Result<(), DatabaseError> {
+        let Some(node) = self.data.get(&id) else {
+            return Err(DatabaseError::UnknownNode(id));
+        };
+        let honest_node_count = self.data.values().filter(|node| node.is_honest()).count();
+        if node.is_honest() && honest_node_count == 1 {
+            return Err(DatabaseError::NoHonestNodes {
+                total: self.data.len() - 1,
+                faulty: self.data.values().filter(|node| node.is_faulty()).count(),
+                malicious: self.data.values().filter(|node| matches!(node.node_type, NodeType::Malicious(_))).count(),
+                observers: self.data.values().filter(|node| node.is_observer()).count(),
+            });
+        }
 
-    let tx_id_set = (0..tx_count).map(|_| TxId::generate() ).collect::<Vec<TxId>>();
-    let tx_set = tx_id_set.clone().into_iter()
-        .zip( vec![TxGlobalStatus::NotFinalized; tx_count] )
-        .collect::<Vec<(TxId, TxGlobalStatus)>>();
+        let neighbors = node.neighborhood.get().clone();
+        for neighbor in &neighbors {
+            self.unlink_nodes(&id, neighbor);
+        }
+        self.data.remove(&id);
+        self.node_set.retain(|(node_id, _, _)| *node_id != id);
+        Ok(())
+    }
 
-    let mut common_preliminary_vision = BTreeMap::new();
-    let center = tx_id_set[0];
-    let mut leaves = tx_id_set.clone();
-    leaves.remove(0);
-    let leaves = Conflicts::new_from(&leaves);
+    // Initializes the opinions of an honest node joining a running simulation:
+    // globally finalized txs get the final opinion of an honest node that finalized them (if one is left), and the other txs are set 
+    // greedily as in initialize_opinions, starting from the txs liked by more honest nodes, 
+    // so that the liked set of the new node is independent.
+    fn initialize_joining_opinions(&self, vision: &mut Vision) {
+        let honest_nodes = self.data.values()
+            .filter(|node| node.is_honest())
+            .collect::<Vec<&Node>>();
+        let mut liked_set = BTreeSet::new();
+        let mut pending_txs = Vec::new();
+        // txs that no node left knows are not in the common vision, so the joining node does not know them either
+        for (tx, status) in &self.tx_set {
+            if !vision.contains(tx) {
+                continue;
+            }
+            // under a threshold policy only some honest nodes may know the tx or have finalized it, 
+            // and remove_node may have removed all of them, leaving the tx to be decided as a pending one
+            let final_opinion = status.finalized().then(|| honest_nodes.iter()
+                .filter_map(|node| node.vision.try_get_opinion_status(tx))
+                .find(|opinion| opinion.is_final()))
+                .flatten();
+            if let Some(&opinion) = final_opinion {
+                if opinion.is_like() {
+                    liked_set.insert(*tx);
+                }
+                vision.set_opinion(tx, opinion);
+            } else {
+                let likes = honest_nodes.iter()
+                    .filter(|node| node.vision.query_opinion(tx) == Some(true))
+                    .count();
+                pending_txs.push((*tx, likes));
+            }
+        }
 
-    common_preliminary_vision.insert(center, (leaves, Opinion::None));
-    let center = Conflicts::new_from(&vec![center]);
+        pending_txs.sort_by_key(|(_, likes)| std::cmp::Reverse(*likes));
+        for (tx, _) in pending_txs {
+            let like = !vision.get_conflict_set(&tx).intersects_set(&liked_set);
+            if like {
+                liked_set.insert(tx);
+            }
+            vision.set_opinion(&tx, Opinion::Pending(like, 0));
+        }
+    }
+
+    // Wires a joining node into a regular topology without changing the degree of the other nodes:
+    // for each pair of links of the new node, a random edge (a, b) is removed and both a and b are linked to the new node.
+    // If degree is odd, or there are not enough such edges, the remaining links go to random nodes.
+    fn link_joining_node(&mut self, node_id: &NodeId, degree: usize) {
+        for _ in 0..degree/2 {
+            let neighborhood = self.data[node_id].neighborhood.get();
+            let edges = self.data.values()
+                .filter(|node| node.id != *node_id && !neighborhood.contains(&node.id))
+                .flat_map(|node| node.neighborhood.iter()
+                    .filter(|neighbor| node.id < **neighbor && *neighbor != node_id && !neighborhood.contains(neighbor))
+                    .map(|neighbor| (node.id, *neighbor)))
+                .collect::<Vec<(NodeId, NodeId)>>();
+            let Some((a, b)) = edges.choose(&mut self.rng).copied() else { break; };
+            self.unlink_nodes(&a, &b);
+            self.link_nodes(node_id, &a);
+            self.link_nodes(node_id, &b);
+        }
 
-    for i in 1..tx_count {
-        common_preliminary_vision.insert(tx_id_set[i], (center.clone(), Opinion::None));
+        while self.data[node_id].neighborhood.len() < degree {
+            let candidates = self.data.keys()
+                .filter(|id| *id != node_id && !self.data[node_id].neighborhood.get().contains(id))
+                .copied()
+                .collect::<Vec<NodeId>>();
+            let Some(neighbor) = candidates.choose(&mut self.rng) else { break; };
+            self.link_nodes(node_id, neighbor);
+        }
     }
 
-    let common_preliminary_vision = Vision::new_from(&common_preliminary_vision);
+    // Builds the neighborhoods of all nodes according to node_graph_type.
+    // It runs after all nodes are inserted, since some topologies depend on the whole set of node ids.
+    // The resulting graph is symmetric and has no self-loops or duplicate edges.
+    fn build_node_graph(&mut self, node_graph_type: NodeGraphType) {
+        let node_id_set = self.node_set.iter()
+            .map(|(id, _, _)| *id)
+            .collect::<Vec<NodeId>>();
+        match node_graph_type {
+            NodeGraphType::Complete => {
+                for (i, node_id) in node_id_set.iter().enumerate() {
+                    for neighbor in &node_id_set[..i] {
+                        self.link_nodes(node_id, neighbor);
+                    }
+                }
+            },
+            NodeGraphType::Random(p) => {
+                for (i, node_id) in node_id_set.iter().enumerate() {
+                    let mut linked = false;
+                    for neighbor in &node_id_set[..i] {
+                        if self.rng.gen_bool(p) {
+                            self.link_nodes(node_id, neighbor);
+                            linked = true;
+                        }
+                    }
+                    // for low probabilities, guarantees that the node is not isolated
+                    if !linked && i > 0 {
+                        let neighbor = node_id_set[self.rng.gen_range(0..i)];
+                        self.link_nodes(node_id, &neighbor);
+                    }
+                }
+            },
+            NodeGraphType::Regular(degree) => self.link_regular_graph(&node_id_set, degree),
+            NodeGraphType::SmallWorld { k, beta } => self.link_small_world_graph(&node_id_set, k, beta),
+            NodeGraphType::Grid { width, height } => self.link_grid_graph(&node_id_set, width, height),
+        }
+    }
 
-    (tx_set, common_preliminary_vision)
-}
+    // Adds a symmetric edge between two distinct nodes, if it does not exist yet
+    fn link_nodes(&mut self, a: &NodeId, b: &NodeId) {
+        if a == b || self.data[a].neighborhood.get().contains(b) { return; }
+        self.data.get_mut(a).unwrap().neighborhood.add(*b);
+        self.data.get_mut(b).unwrap().neighborhood.add(*a);
+    }
 
-pub enum LikeDistributions{
-    Equal,
-    Concentrated(usize)
-}
+    // Replaces the node graph with the given adjacency list (e.g. read by load_node_graph), 
+    // linking each node to each of its listed neighbors in both directions; nodes that are not listed 
+    // keep only the links listed by others. Fails without changing anything if some node is not in the database.
+    pub fn set_node_graph(&mut self, adjacency: &[(NodeId, Vec<NodeId>)]) -> Result<(), DatabaseError> {
+        let unknown = adjacency.iter()
+            .flat_map(|(node, neighbors)| std::iter::once(node).chain(neighbors))
+            .find(|node| !self.data.contains_key(node));
+        if let Some(node) = unknown {
+            return Err(DatabaseError::UnknownNode(*node));
+        }
+
+        for node in self.data.values_mut() {
+            node.neighborhood = Neighborhood::new();
+        }
+        for (node, neighbors) in adjacency {
+            for neighbor in neighbors {
+                self.link_nodes(node, neighbor);
+            }
+        }
+        Ok(())
+    }
 
+    // Removes the symmetric edge between two nodes
+    fn unlink_nodes(&mut self, a: &NodeId, b: &NodeId) {
+        self.data.get_mut(a).unwrap().neighborhood.remove(b);
+        self.data.get_mut(b).unwrap().neighborhood.remove(a);
+    }
 
-#[derive(Debug, PartialEq, Clone)]
-pub enum NodeType {
-    Malicious, 
-    Faulty,
-    Regular
-}
\ No newline at end of file
+    // NodeGraphType::Regular: repeatedly links random pairs of nodes that have 
+    // less than degree neighbors and are not yet neighbors, until no such pair exists.
+    // Some nodes may end up with a smaller degree (e.g. if degree times the number of nodes is odd).
+    fn link_regular_graph(&mut self, node_id_set: &[NodeId], degree: usize) {
+        loop {
+            let mut open_nodes = node_id_set.iter()
+                .filter(|id| self.data[id].neighborhood.len() < degree)
+                .collect::<Vec<NodeId>>();
+            open_nodes.shuffle(&mut self.rng);
+
+            let mut linked = false;
+            for node_id in &open_nodes {
+                if self.data[node_id].neighborhood.len() >= degree { continue; }
+                let candidates = open_nodes.iter()
+                    .filter(|id| *id != node_id 
+                        && self.data[id].neighborhood.len() < degree 
+                        && !self.data[node_id].neighborhood.get().contains(id))
+                    .collect::<Vec<NodeId>>();
+                if let Some(neighbor) = candidates.choose(&mut self.rng) {
+                    self.link_nodes(node_id, neighbor);
+                    linked = true;
+                }
+            }
+            if !linked { break; }
+        }
+    }
+
+    // NodeGraphType::SmallWorld (Watts-Strogatz): builds a ring lattice where each node is linked 
+    // to its k nearest neighbors (k/2 on each side), then rewires each lattice edge, with probability beta,
+    // to a random node that is not yet a neighbor.
+    fn link_small_world_graph(&mut self, node_id_set: &[NodeId], k: usize, beta: f64) {
+        let node_count = node_id_set.len();
+        for i in 0..node_count {
+            for j in 1..=k/2 {
+                self.link_nodes(&node_id_set[i], &node_id_set[(i+j) % node_count]);
+            }
+        }
+
+        for i in 0..node_count {
+            for j in 1..=k/2 {
+                let node_id = node_id_set[i];
+                let old_neighbor = node_id_set[(i+j) % node_count];
+                if !self.rng.gen_bool(beta) { continue; }
+                let candidates = node_id_set.iter()
+                    .filter(|id| **id != node_id && !self.data[&node_id].neighborhood.get().contains(id))
+                    .collect::<Vec<NodeId>>();
+                if let Some(new_neighbor) = candidates.choose(&mut self.rng) {
+                    self.unlink_nodes(&node_id, &old_neighbor);
+                    self.link_nodes(&node_id, new_neighbor);
+                }
+            }
+        }
+    }
+
+    // NodeGraphType::Grid: places the nodes row by row, in order of creation, on a width x height torus,
+    // and links each node to the next one in its row and in its column (wrapping around).
+    // In grids with a side of 1 or 2, nodes have less than 4 distinct neighbors.
+    fn link_grid_graph(&mut self, node_id_set: &[NodeId], width: usize, height: usize) {
+        for y in 0..height {
+            for x in 0..width {
+                let node_id = node_id_set[y*width + x];
+                self.link_nodes(&node_id, &node_id_set[y*width + (x+1) % width]);
+                self.link_nodes(&node_id, &node_id_set[((y+1) % height)*width + x]);
+            }
+        }
+    }
+
+    // Number of rounds run so far; during a round, the index of the current round (starting at 1)
+    pub fn round(&self) -> u32 {
+        self.round
+    }
+
+    // Ids of all txs, in the order they were generated or added
+    // (the order of the probabilities of LikeDistributions::Weighted)
+    pub fn tx_ids(&self) -> Vec<TxId> {
+        self.tx_set.iter().map(|(tx, _)| *tx).collect()
+    }
+
+    // Current opinions of a node, by tx; None if the node is not in the database
+    pub fn node_opinions(&self, id: NodeId) -> Option<Vec<(TxId, Opinion)>> {
+        let node = self.data.get(&id)?;
+        Some(node.vision.get_txs().into_iter()
+            .map(|tx| (tx, *node.vision.get_opinion_status(&tx)))
+            .collect())
+    }
+
+    // Current status of a node; None if the node is not in the database
+    pub fn node_status(&self, id: NodeId) -> Option<NodeStatus> {
+        self.data.get(&id).map(|node| node.status)
+    }
+
+    // Round in which each globally finalized tx was finalized by all honest nodes
+    pub fn finalization_rounds(&self) -> &BTreeMap<TxId, u32> {
+        &self.finalization_rounds
+    }
+
+    // Number of queries sent by the honest nodes and observers over the whole run (see RoundMetrics::query_count)
+    pub fn total_query_count(&self) -> u64 {
+        self.total_query_count
+    }
+
+    // Fraction of honest nodes among all the nodes (faulty nodes, malicious nodes and observers included)
+    pub fn safety_margin(&self) -> f64 {
+        let (honest, _) = self.responder_counts();
+        honest as f64 / self.node_set.len() as f64
+    }
+
+    // Checks the tolerance assumption of FPC: the malicious nodes must be less than a fraction BETA 
+    // of the nodes that answer queries, so that they cannot move the share of likes of a tx across 
+    // the whole range [BETA, 1-BETA] of the random threshold, i.e. malicious < BETA * (honest + malicious),
+    // with the BETA of the next round (config.beta unless a schedule is set). Without malicious nodes 
+    // the bound always holds, even with BETA = 0.
+    // The bound does not check safety_margin, which is a different quantity: faulty nodes and observers 
+    // never answer, so they do not count here, while safety_margin counts them among all the nodes.
+    pub fn is_within_safety_bound(&self) -> bool {
+        let (honest, malicious) = self.responder_counts();
+        malicious == 0 || (malicious as f64) < self.beta(self.round + 1) * (honest + malicious) as f64
+    }
+
+    // Numbers of honest and malicious nodes
+    fn responder_counts(&self) -> (usize, usize) {
+        self.node_set.iter()
+            .fold((0, 0), |(honest, malicious), (_, node_type, _)| match node_type {
+                NodeType::Regular => (honest + 1, malicious),
+                NodeType::Malicious(_) => (honest, malicious + 1),
+                _ => (honest, malicious),
+            })
+    }
+
+    // Queries sent by the honest nodes and observers per tx finalized by all honest nodes, 
+    // None if no tx is finalized yet
+    pub fn efficiency(&self) -> Option<f64> {
+        let finalized_tx_count = self.tx_set.iter()
+            .filter(|(_, status)| status.finalized())
+            .count();
+        (finalized_tx_count > 0).then(|| self.total_query_count as f64 / finalized_tx_count as f64)
+    }
+
+    // Number of times the opinion of each honest node on each tx flipped between like and dislike;
+    // pairs that never flipped are left out
+    pub fn flip_counts(&self) -> BTreeMap<(NodeId, TxId), u32> {
+        self.data.values()
+            .filter(|node| node.is_honest())
+            .flat_map(|node| node.flip_counts.iter().map(|(tx, count)| ((node.id, *tx), *count)))
+            .collect()
+    }
+
+    // Metrics sampled at the end of each round, in order
+    pub fn metrics_history(&self) -> &[RoundMetrics] {
+        &self.metrics_history
+    }
+
+    // Gathers the outcome of the run so far (see ConsensusSummary)
+    pub fn summary(&self) -> ConsensusSummary {
+        let honest_nodes = self.data.values()
+            .filter(|node| node.is_honest())
+            .collect::<Vec<&Node>>();
+        let finalized_txs = self.tx_set.iter()
+            .filter(|(_, status)| status.finalized())
+            .map(|(tx, _)| *tx)
+            .collect::<Vec<TxId>>();
+
+        let mut outcomes = BTreeMap::new();
+        let mut min_agreement_rate: Option<f64> = None;
+        for tx in &finalized_txs {
+            let likes = honest_nodes.iter()
+                .filter(|node| node.vision.query_opinion(tx) == Some(true))
+                .count();
+            let agreement_rate = agreement_rate(tx, &honest_nodes);
+            outcomes.insert(*tx, 2*likes > honest_nodes.len());
+            min_agreement_rate = Some(min_agreement_rate.map_or(agreement_rate, |rate| rate.min(agreement_rate)));
+        }
+
+        ConsensusSummary {
+            finalized_tx_count: finalized_txs.len(),
+            finalized_honest_node_count: honest_nodes.iter().filter(|node| node.status.finalized()).count(),
+            outcomes,
+            min_agreement_rate,
+            cluster_winners: self.cluster_winners(),
+            efficiency: self.efficiency(),
+        }
+    }
+
+    // State of every tx in the view of the honest majority, see LedgerState
+    pub fn ledger(&self) -> BTreeMap<TxId, LedgerState> {
+        let honest_nodes = self.data.values()
+            .filter(|node| node.is_honest())
+            .collect::<Vec<&Node>>();
+        self.tx_set.iter()
+            .map(|(tx, status)| {
+                if !status.finalized() {
+                    return (*tx, LedgerState::Pending);
+                }
+                let likes = honest_nodes.iter()
+                    .filter(|node| node.vision.query_opinion(tx) == Some(true))
+                    .count();
+                if 2*likes > honest_nodes.len() {
+                    (*tx, LedgerState::Accepted)
+                } else {
+                    (*tx, LedgerState::Rejected)
+                }
+            })
+            .collect()
+    }
+
+    // Statistics of the finalization rounds of the txs, see LatencyStats
+    pub fn latency_stats(&self) -> LatencyStats {
+        let mut latencies = self.finalization_rounds.values().copied().collect::<Vec<u32>>();
+        latencies.sort();
+        let count = latencies.len();
+        let mean = (count > 0).then(|| latencies.iter().map(|rounds| *rounds as f64).sum::<f64>() / count as f64);
+        let median = (count > 0).then(|| {
+            if count % 2 == 1 {
+                latencies[count/2] as f64
+            } else {
+                (latencies[count/2 - 1] + latencies[count/2]) as f64 / 2.0
+            }
+        });
+        let unfinalized = self.tx_set.iter()
+            .filter(|(tx, _)| !self.finalization_rounds.contains_key(tx))
+            .map(|(tx, _)| *tx)
+            .collect();
+
+        LatencyStats {
+            mean,
+            median,
+            max: latencies.last().copied(),
+            unfinalized,
+        }
+    }
+
+    // Each connected component of the conflict graph (sorted by TxId, in order of their smallest TxId), 
+    // with the tx finalized as liked by all honest nodes in it, or None if there is none yet
+    pub fn cluster_winners(&self) -> Vec<(Vec<TxId>, Option<TxId>)> {
+        let honest_nodes = self.data.values()
+            .filter(|node| node.is_honest())
+            .collect::<Vec<&Node>>();
+        self.conflict_components().into_iter()
+            .map(|component| {
+                let winner = component.iter()
+                    .find(|tx| honest_nodes.iter()
+                        .all(|node| matches!(node.vision.try_get_opinion_status(tx), Some(Opinion::Final(true)))))
+                    .copied();
+                (component, winner)
+            })
+            .collect()
+    }
+
+    // Each connected component of the conflict graph (as in cluster_winners), with the lowest 
+    // agreement rate of the honest nodes over its txs (see agreement_rate)
+    pub fn cluster_agreement_rates(&self) -> Vec<(Vec<TxId>, f64)> {
+        let honest_nodes = self.data.values()
+            .filter(|node| node.is_honest())
+            .collect::<Vec<&Node>>();
+        self.conflict_components().into_iter()
+            .map(|component| {
+                let rate = component.iter()
+                    .map(|tx| agreement_rate(tx, &honest_nodes))
+                    .fold(1.0, f64::min);
+                (component, rate)
+            })
+            .collect()
+    }
+
+    // Connected components of the conflict graph (which is the same for all nodes), 
+    // each sorted by TxId, in order of their smallest TxId
+    fn conflict_components(&self) -> Vec<Vec<TxId>> {
+        self.common_vision().connected_components()
+    }
+
+    // Serializes the whole state of the database, including the round counter, the metrics 
+    // and the rng state, so that a database restored by from_json continues the run seamlessly.
+    // Fails if the malicious nodes follow a custom (not built-in) AdversaryStrategy.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    // Restores a database serialized by to_json. The event handler and the beta schedule are not serialized
+    // and are reset to the default ones.
+    #[cfg(feature = "serde")]
+    pub fn from_json(json: &str) -> Result<Database, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    // Deep copy of the database, including the round counter and the metrics, to run branches of the 
+    // same simulation. The rng is copied, not shared: both copies draw the same random numbers from here on,
+    // so they evolve identically unless they are changed (use snapshot_with_seed to fork the randomness).
+    // The event handler is not copied; the snapshot gets the default one.
+    pub fn snapshot(&self) -> Database {
+        Database {
+            data: self.data.clone(),
+            tx_set: self.tx_set.clone(),
+            node_set: self.node_set.clone(),
+            config: self.config,
+            node_graph_type: self.node_graph_type,
+            rng: self.rng.clone(),
+            seed: self.seed,
+            round: self.round,
+            finalization_rounds: self.finalization_rounds.clone(),
+            metrics_history: self.metrics_history.clone(),
+            finalization_thresholds: self.finalization_thresholds.clone(),
+            total_query_count: self.total_query_count,
+            opinion_history: self.opinion_history.clone(),
+            adversary_plan: self.adversary_plan.clone(),
+            sequential_ids: self.sequential_ids,
+            beta_schedule: self.beta_schedule.clone(),
+            event_handler: EventHandler::default()
+        }
+    }
+
+    // Like snapshot, but the copy gets seed as its master seed and all its rngs are reseeded from it, 
+    // so the branch is deterministic and independent of the original
+    pub fn snapshot_with_seed(&self, seed: u64) -> Database {
+        let mut snapshot = self.snapshot();
+        snapshot.rng = ChaCha12Rng::seed_from_u64(seed);
+        snapshot.seed = seed;
+        for node in snapshot.data.values_mut() {
+            node.rng = Database::node_rng(seed, node.id);
+        }
+        snapshot
+    }
+
+    // Registers the callback that receives the events of the following rounds, replacing the previous one
+    pub fn set_event_handler(&mut self, handler: impl Fn(&Event) + Send + Sync + 'static) {
+        self.event_handler = EventHandler::new(handler);
+    }
+
+    // Makes each round use the BETA returned by schedule for its index (starting at 1), instead of config.beta.
+    // Values outside [0, 0.5] are clamped to it.
+    pub fn set_beta_schedule(&mut self, schedule: impl Fn(u32) -> f64 + Send + Sync + 'static) {
+        self.beta_schedule = Some(BetaSchedule::new(schedule));
+    }
+
+    // Plan of the malicious nodes for the current round, see AdversaryStrategy::plan
+    pub fn adversary_plan(&self) -> Option<&AdversaryController> {
+        self.adversary_plan.as_ref()
+    }
+
+    // BETA used in the given round
+    pub fn beta(&self, round: u32) -> f64 {
+        match &self.beta_schedule {
+            Some(schedule) => schedule.beta(round).clamp(0.0, 0.5),
+            None => self.config.beta,
+        }
+    }
+
+    pub fn run_fpcs_round(&mut self){
+        let beta = self.beta(self.round + 1);
+        let random_number = scale_random_number(self.rng.next_u32(), beta);
+        let order_random_number = if self.config.separate_order_random { self.rng.next_u32() } else { random_number };
+        self.run_fpcs_round_with_order(random_number, order_random_number);
+    }
+
+    // Runs a round with the given random number instead of drawing it from the rng: 
+    // the threshold of a tx is random_number/u32::MAX of the responses (so random_number is used as is, 
+    // not scaled into [BETA, 1-BETA]). The rng is still used to sample the queried nodes.
+    // The same random number orders the txs in elim and comp, see run_fpcs_round_with_order.
+    pub fn run_fpcs_round_with(&mut self, random_number: u32){
+        self.run_fpcs_round_with_order(random_number, random_number);
+    }
+
+    // Same as run_fpcs_round_with, but the txs are ordered in elim and comp with order_random_number 
+    // instead of the random number of the threshold, so the order can be kept fixed while the threshold varies
+    pub fn run_fpcs_round_with_order(&mut self, random_number: u32, order_random_number: u32){
+        // observers query like honest nodes
+        let querying_nodes = self.node_set.iter()
+            .filter(|(_, node_type, status)| !status.finalized() 
+                && (*node_type == NodeType::Regular || *node_type == NodeType::Observer))
+            .map(|(id,_,_)| id)
+            .collect::<Vec<NodeId>>();
+        self.run_round_of(&querying_nodes, random_number, order_random_number);
+    }
+
+    // Runs a round, with the given random number, in which only the given nodes query and update their opinions, 
+    // while all other nodes keep them (they still answer queries). Nodes that are not honest, 
+    // already finalized or unknown are ignored. The global finalization of txs and nodes is updated as usual.
+    // Returns what changed in the round, for the given nodes only.
+    pub fn run_round_for(&mut self, nodes: &[NodeId], random_number: u32) -> RoundDelta {
+        let querying_nodes = nodes.iter()
+            .filter(|id| self.data.get(id).is_some_and(|node| node.is_honest() && !node.status.finalized()))
+            .copied()
+            .collect::<BTreeSet<NodeId>>();
+        let node_list = querying_nodes.iter().copied().collect::<Vec<NodeId>>();
+        self.run_and_compare(&querying_nodes, |database| database.run_round_of(&node_list, random_number, random_number))
+    }
+
+    // Runs a round in which only querying_nodes query and update their opinions
+    fn run_round_of(&mut self, querying_nodes: &[NodeId], random_number: u32, order_random_number: u32){
+        self.round += 1;
+        let mut events = Vec::new();
+
+        // all malicious nodes share the same strategy, so one plan coordinates them all
+        let strategy = self.data.values().find_map(|node| match &node.node_type {
+            NodeType::Malicious(strategy) => Some(strategy.clone()),
+            _ => None,
+        });
+        self.adversary_plan = strategy.and_then(|strategy| strategy.plan(self.round, self));
+
+        // An isolated node (with an empty neighborhood) cannot query anyone, so it keeps its opinions
+        // as they are, without counting the round as an agreement
+        let querying_nodes = querying_nodes.iter()
+            .filter(|id| !self.data[id].neighborhood.is_empty())
+            .collect::<Vec<NodeId>>();
+        let unfinalized_txs = self.tx_set.iter()
+            .filter(|(_, status)| !status.finalized())
+            .map(|(id, _)| id)
+            .collect::<Vec<TxId>>();
+        let weights = self.config.weighted_sampling.then(|| self.data.values()
+            .map(|node| (node.id, node.weight))
+            .collect::<BTreeMap<NodeId, u64>>());
+
+        // skipped rounds, samples and whether unreliable nodes respond are drawn from the rng of the querying node, 
+        // so they do not depend on the order in which the nodes are processed
+        let mut query_count = 0;
+        let mut node_samples = Vec::new();
+        for node_id in querying_nodes {
+            let node = &self.data[&node_id];
+            let mut rng = node.rng.clone();
+            // a lazy node that skips the round keeps its opinions, as an isolated node
+            let skips = node.skip_prob > 0.0 && rng.gen_bool(node.skip_prob.min(1.0));
+            if !skips {
+                let k = node.query_count.unwrap_or(self.config.k);
+                let node_sample = node.sample_from_neighborhood(k, weights.as_ref(), &mut rng);
+                query_count += node_sample.len();
+                let node_sample = node_sample
+                    .into_iter()
+                    .filter(|queried_node| {
+                        let reliability = self.data[queried_node].reliability;
+                        reliability >= 1.0 || rng.gen_bool(reliability.max(0.0))
+                    })
+                    .collect::<Vec<NodeId>>();
+                node_samples.push((node_id, node_sample));
+            }
+            self.data.get_mut(&node_id).unwrap().rng = rng;
+        }
+
+        // all nodes collect their new opinions from the state of the database at the beginning of the round,
+        // and the new opinions are then applied in a fixed order (synchronous rounds: no node sees the update 
+        // of another node of the same round, with or without the parallel feature)
+        let mut opinion_changes = BTreeMap::new();
+        let mut step_counts = StepCounts::default();
+        for (node_id, (new_opinions, node_step_counts)) in self.collect_new_opinions(node_samples, random_number, order_random_number) {
+            step_counts.elim_removed += node_step_counts.elim_removed;
+            step_counts.comp_added += node_step_counts.comp_added;
+            let node = self.data.get_mut(&node_id).unwrap();
+            let mut changes = node.update_opinions(new_opinions, self.config.l, &self.finalization_thresholds, self.round);
+            for SafetyViolation { node, tx, conflict } in changes.safety_violations.drain(..) {
+                events.push(Event::SafetyViolated { node, tx, conflict, round: self.round });
+            }
+            opinion_changes.insert(node_id, changes);
+        }
+        self.opinion_history.push_back(opinion_changes);
+        if self.opinion_history.len() > HISTORY_ROUNDS {
+            self.opinion_history.pop_front();
+        }
+
+        self.complete_round(&unfinalized_txs, query_count, step_counts, events);
+    }
+
+    // End of a round, after the opinions are updated: updates the global status of the txs that 
+    // were not finalized at its beginning and of the nodes, records the metrics and emits the events
+    fn complete_round(&mut self, unfinalized_txs: &[TxId], query_count: usize, step_counts: StepCounts, mut events: Vec<Event>) {
+        self.update_statuses(&mut events);
+
+        let honest_nodes = self.node_set.iter()
+            .filter(|(_, node_type, _)| *node_type == NodeType::Regular)
+            .map(|(id,_,_)| id)
+            .collect::<Vec<NodeId>>();
+        let like_fractions = unfinalized_txs.iter()
+            .map(|tx| {
+                let likes = honest_nodes.iter()
+                    .filter(|node_id| self.data.get(node_id).unwrap().vision.query_opinion(tx) == Some(true))
+                    .count();
+                (*tx, likes as f64 / honest_nodes.len() as f64)
+            })
+            .collect::<BTreeMap<TxId, f64>>();
+        let finalized_tx_count = self.tx_set.iter()
+            .filter(|(_, status)| status.finalized())
+            .count();
+        let finalized_node_count = self.node_set.iter()
+            .filter(|(_, node_type, status)| *node_type == NodeType::Regular && status.finalized())
+            .count();
+        self.total_query_count += query_count as u64;
+        self.metrics_history.push(RoundMetrics { 
+            round: self.round, 
+            like_fractions, 
+            query_count, 
+            finalized_tx_count, 
+            finalized_node_count, 
+            elim_removed: step_counts.elim_removed, 
+            comp_added: step_counts.comp_added,
+        });
+
+        events.push(Event::RoundCompleted { round: self.round });
+        for event in &events {
+            self.event_handler.handle(event);
+        }
+
+    }
+
+    // Finalizes (globally) the txs that are not finalized yet but are final for enough honest nodes
+    // (see FinalizationPolicy), 
+    // and copies the status of the honest nodes and observers into node_set, 
+    // pushing the events of the txs and honest nodes that got finalized
+    fn update_statuses(&mut self, events: &mut Vec<Event>) {
+        let newly_finalized = self.tx_set.iter()
+            .filter(|(tx, status)| !status.finalized() && self.globally_finalized(tx))
+            .map(|(tx, _)| *tx)
+            .collect::<Vec<TxId>>();
+        let honest_nodes = self.data.values()
+            .filter(|node| node.is_honest())
+            .collect::<Vec<&Node>>();
+        for tx in &newly_finalized {
+            let agreement_rate = agreement_rate(tx, &honest_nodes);
+            events.push(Event::TxFinalized { tx: *tx, round: self.round, agreement_rate });
+        }
+        for (tx, status) in self.tx_set.iter_mut().filter(|(tx, _)| newly_finalized.contains(tx)) {
+            *status = TxGlobalStatus::Finalized;
+            self.finalization_rounds.insert(*tx, self.round);
+        }
+
+        for (id, node_type , status) in self.node_set.iter_mut() {
+            if *node_type == NodeType::Regular {
+                let old_status = *status;
+                *status = self.data.get(id).unwrap().status;
+                if !old_status.finalized() && status.finalized() {
+                    events.push(Event::NodeFinalized { node: *id, round: self.round });
+                }
+            } else if *node_type == NodeType::Observer {
+                *status = self.data.get(id).unwrap().status;
+            }
+        }
+    }
+
+    // Whether enough honest nodes have a final opinion on tx for it to be finalized globally, 
+    // according to config.finalization_policy
+    // (a tx that is still propagating is not finalized by the nodes that do not know it)
+    fn globally_finalized(&self, tx: &TxId) -> bool {
+        let honest_nodes = self.data.values().filter(|node| node.is_honest());
+        let is_final = |node: &Node| node.vision.try_get_opinion_status(tx).is_some_and(|opinion| opinion.is_final());
+        match self.config.finalization_policy {
+            FinalizationPolicy::Unanimous => honest_nodes.into_iter().all(is_final),
+            FinalizationPolicy::Threshold(fraction) => {
+                let (finalized, total) = honest_nodes.fold((0, 0), |(finalized, total), node| {
+                    (finalized + is_final(node) as usize, total + 1)
+                });
+                finalized as f64 >= fraction * total as f64
+            },
+            FinalizationPolicy::StakeThreshold(fraction) => {
+                let (finalized, total) = honest_nodes.fold((0, 0), |(finalized, total), node| {
+                    (finalized + if is_final(node) { node.weight } else { 0 }, total + node.weight)
+                });
+                finalized as f64 >= fraction * total as f64
+            },
+        }
+    }
+
+    // Brings the status of the nodes and the global status of the txs up to date with the opinions of the nodes, 
+    // without running a round, e.g. after setting opinions directly: each honest node (or observer) is finalized 
+    // if all its opinions are final, and a tx is finalized according to config.finalization_policy 
+    // (a tx that no longer is goes back to not finalized). Emits the events of what got finalized.
+    pub fn recompute_statuses(&mut self) {
+        for node in self.data.values_mut().filter(|node| node.is_honest() || node.is_observer()) {
+            node.status = if node.vision.has_finalized() { NodeStatus::Finalized } else { NodeStatus::NotFinalized };
+        }
+        let reverted = self.tx_set.iter()
+            .filter(|(tx, status)| status.finalized() && !self.globally_finalized(tx))
+            .map(|(tx, _)| *tx)
+            .collect::<Vec<TxId>>();
+        for (tx, status) in self.tx_set.iter_mut().filter(|(tx, _)| reverted.contains(tx)) {
+            *status = TxGlobalStatus::NotFinalized;
+            self.finalization_rounds.remove(tx);
+        }
+
+        let mut events = Vec::new();
+        self.update_statuses(&mut events);
+        for event in &events {
+            self.event_handler.handle(event);
+        }
+    }
+
+    // Checks the safety invariant of FPCS on a set: no honest node has a tx finalized as liked
+    // while one of its conflicts is also finalized as liked.
+    // Returns the first violation found (nodes ordered by id, txs ordered by id).
+    pub fn check_safety(&self) -> Result<(), SafetyViolation> {
+        for node in self.data.values().filter(|node| node.is_honest() ) {
+            if let Some(violation) = node.find_safety_violation() {
+                return Err(violation);
+            }
+        }
+        Ok(())
+    }
+
+    // Computes the new opinions of each node from its sample, in the order of node_samples
+    #[cfg(not(feature = "parallel"))]
+    fn collect_new_opinions(&self, node_samples: Vec<(NodeId, Vec<NodeId>)>, random_number: u32, order_random_number: u32) -> Vec<(NodeId, NewOpinions)> {
+        node_samples.into_iter()
+            .map(|(node_id, node_sample)| (node_id, self.data[&node_id].collect_and_set_new_opinion(node_sample, self, random_number, order_random_number)))
+            .collect()
+    }
+
+    // Computes the new opinions of each node from its sample, in parallel, in the order of node_samples
+    #[cfg(feature = "parallel")]
+    fn collect_new_opinions(&self, node_samples: Vec<(NodeId, Vec<NodeId>)>, random_number: u32, order_random_number: u32) -> Vec<(NodeId, NewOpinions)> {
+        node_samples.into_par_iter()
+            .map(|(node_id, node_sample)| (node_id, self.data[&node_id].collect_and_set_new_opinion(node_sample, self, random_number, order_random_number)))
+            .collect()
+    }
+
+    // Runs one round and returns what changed in it, by comparing the honest nodes before and after the round
+    pub fn step(&mut self) -> RoundDelta {
+        let honest_nodes = self.data.values()
+            .filter(|node| node.is_honest())
+            .map(|node| node.id)
+            .collect::<BTreeSet<NodeId>>();
+        self.run_and_compare(&honest_nodes, |database| database.run_fpcs_round())
+    }
+
+    // Calls run and returns what changed in it for the given honest nodes, by comparing them before and after
+    fn run_and_compare(&mut self, nodes: &BTreeSet<NodeId>, run: impl FnOnce(&mut Database)) -> RoundDelta {
+        let likes_before = self.data.values()
+            .filter(|node| nodes.contains(&node.id))
+            .flat_map(|node| node.vision.get_txs().into_iter()
+                .map(move |tx| ((node.id, tx), node.vision.get_opinion(tx))))
+            .collect::<BTreeMap<(NodeId, TxId), bool>>();
+        let finalized_before = self.data.values()
+            .filter(|node| nodes.contains(&node.id) && node.status.finalized())
+            .map(|node| node.id)
+            .collect::<BTreeSet<NodeId>>();
+
+        run(self);
+
+        let flipped_opinions = likes_before.into_iter()
+            .filter_map(|((node_id, tx), like)| {
+                let new_like = self.data.get(&node_id)?.vision.get_opinion(tx);
+                (new_like != like).then_some((node_id, tx, new_like))
+            })
+            .collect();
+        let finalized_txs = self.finalization_rounds.iter()
+            .filter(|(_, round)| **round == self.round)
+            .map(|(tx, _)| *tx)
+            .collect();
+        let finalized_nodes = self.data.values()
+            .filter(|node| nodes.contains(&node.id) && node.status.finalized() && !finalized_before.contains(&node.id))
+            .map(|node| node.id)
+            .collect();
+
+        RoundDelta { round: self.round, flipped_opinions, finalized_txs, finalized_nodes }
+    }
+
+    // Runs rounds lazily, see Rounds
+    pub fn rounds(&mut self) -> Rounds<'_> {
+        Rounds { database: self }
+    }
+
+    // Runs rounds until all honest nodes finalize, or until max_rounds rounds have been run by this call
+    pub fn run_until_final(&mut self, max_rounds: usize) -> RunOutcome {
+        for _ in 0..max_rounds {
+            if self.is_final() {
+                return RunOutcome::Converged(self.round);
+            }
+            self.run_fpcs_round();
+        }
+        if self.is_final() {
+            RunOutcome::Converged(self.round)
+        } else {
+            RunOutcome::Stalled(self.round)
+        }
+    }
+
+    // Runs rounds until the agreement rate of every component of the conflict graph 
+    // (see cluster_agreement_rates) is at least target, even if the txs are not finalized yet,
+    // or until max_rounds rounds are run. Converged means that every component met the target.
+    // Also returns the components that met the target.
+    pub fn run_until_agreement(&mut self, target: f64, max_rounds: usize) -> (RunOutcome, Vec<Vec<TxId>>) {
+        let mut rounds_run = 0;
+        loop {
+            let rates = self.cluster_agreement_rates();
+            let all_met = rates.iter().all(|(_, rate)| *rate >= target);
+            if all_met || rounds_run == max_rounds {
+                let met = rates.into_iter()
+                    .filter(|(_, rate)| *rate >= target)
+                    .map(|(component, _)| component)
+                    .collect();
+                let outcome = if all_met { RunOutcome::Converged(self.round) } else { RunOutcome::Stalled(self.round) };
+                return (outcome, met);
+            }
+            self.run_fpcs_round();
+            rounds_run += 1;
+        }
+    }
+
+    // Checks if some honest node is stuck in the last window rounds: it did not finalize any tx,
+    // and its opinion on some tx flipped at least twice (back and forth).
+    // Only the last HISTORY_ROUNDS rounds are kept, so larger windows are clamped;
+    // returns false if less than window rounds were run.
+    pub fn is_oscillating(&self, window: usize) -> bool {
+        let window = window.min(HISTORY_ROUNDS);
+        if window == 0 || self.opinion_history.len() < window {
+            return false;
+        }
+
+        let mut flips = BTreeMap::<(NodeId, TxId), usize>::new();
+        let mut progressed = BTreeSet::new();
+        for round_changes in self.opinion_history.range(self.opinion_history.len() - window..) {
+            for (node_id, changes) in round_changes {
+                if changes.finalized > 0 {
+                    progressed.insert(*node_id);
+                }
+                for tx in &changes.flipped {
+                    *flips.entry((*node_id, *tx)).or_default() += 1;
+                }
+            }
+        }
+
+        flips.iter()
+            .any(|((node_id, _), count)| *count >= 2 
+                && !progressed.contains(node_id) 
+                && self.data.contains_key(node_id))
+    }
+
+    // Whether the honest nodes agree on the outcome of every tx they finalized: no tx is finalized
+    // as liked by an honest node and as disliked by another, and no honest node finalized two conflicting txs
+    // as liked, so every conflict cluster has the same finalized winners in all honest nodes.
+    // Unlike is_final, it does not require the nodes to have finalized everything.
+    pub fn has_consensus(&self) -> bool {
+        if self.check_safety().is_err() {
+            return false;
+        }
+        let honest_nodes = self.data.values()
+            .filter(|node| node.is_honest())
+            .collect::<Vec<&Node>>();
+        self.tx_set.iter().all(|(tx, _)| {
+            let mut outcomes = honest_nodes.iter()
+                .filter_map(|node| match node.vision.try_get_opinion_status(tx) {
+                    Some(Opinion::Final(like)) => Some(*like),
+                    _ => None,
+                });
+            match outcomes.next() {
+                Some(first) => outcomes.all(|like| like == first),
+                None => true,
+            }
+        })
+    }
+
+    pub fn is_final(&self) -> bool {
+        for (_, node_type, status) in &self.node_set {
+            if !status.finalized() && *node_type == NodeType::Regular { return false; }
+        } 
+        true
+    }
+
+    pub fn print_results(&self) {
+        for node in self.data.values(){
+            if node.is_honest(){
+                let txs = node.vision.get_txs();
+                let opinion = txs.iter()
+                    .map(|tx| format!("{}: {:?}", tx, node.vision.get_opinion_status(tx)))
+                    .collect::<Vec<String>>(); 
+                println!("node {}: Status {:?}", node.id, node.status);
+                println!("Current vision: [{}]", opinion.join(", "));
+            }
+        }
+    }
+
+    // Compact view of the opinions: one line per honest node, one column per tx (sorted by id), with
+    // '+' for Final(true), '-' for Final(false), '▲'/'▽' for a pending like/dislike, '.' for no opinion
+    // and ' ' for a tx unknown to the node
+    pub fn render_ascii(&self) -> String {
+        let mut txs = self.tx_set.iter()
+            .map(|(id, _)| *id)
+            .collect::<Vec<TxId>>();
+        txs.sort();
+        let honest_count = self.data.values().filter(|node| node.is_honest()).count();
+        // "node " + 6 hex digits + " " + up to 3 bytes per tx + newline
+        let mut output = String::with_capacity(honest_count * (13 + 3 * txs.len()));
+        for node in self.data.values().filter(|node| node.is_honest()) {
+            let _ = write!(output, "node {} ", node.id);
+            for tx in &txs {
+                output.push(match node.vision.try_get_opinion_status(tx) {
+                    Some(Opinion::Final(true)) => '+',
+                    Some(Opinion::Final(false)) => '-',
+                    Some(Opinion::Pending(true, _)) => '▲',
+                    Some(Opinion::Pending(false, _)) => '▽',
+                    Some(Opinion::None) => '.',
+                    None => ' ',
+                });
+            }
+            output.push('\n');
+        }
+        output
+    }
+
+}
+
+
+
+// Fraction of honest_nodes that agree with the majority on tx (like or not like, 
+// a node that has no opinion on tx or does not know it counts as not liking it)
+fn agreement_rate(tx: &TxId, honest_nodes: &[&Node]) -> f64 {
+    let likes = honest_nodes.iter()
+        .filter(|node| node.vision.query_opinion(tx) == Some(true))
+        .count();
+    (likes.max(honest_nodes.len()-likes) as f64)/(honest_nodes.len() as f64)
+}
+
+// Maps a random number drawn in 0..=u32::MAX to [BETA * u32::MAX, (1-BETA) * u32::MAX], 
+// so that the threshold of the round (see threshold) is a fraction of the responses in [BETA, 1-BETA]
+// (with BETA = 0.5, the interval holds no integer, and the result is u32::MAX/2, the closest one below)
+fn scale_random_number(random_number: u32, beta: f64) -> u32 {
+    let random_interval_length = 1.0 - 2.0*beta;
+    // float rounding could take the extremes slightly out of [BETA, 1-BETA], so the result is clamped
+    // (not with f64::clamp, which panics when the bounds cross, e.g. with BETA = 0.5)
+    let lower_bound = (u32::MAX as f64 * beta).ceil();
+    let upper_bound = (u32::MAX as f64 * (1.0 - beta)).floor();
+    (random_number as f64 * random_interval_length + u32::MAX as f64 * beta).floor()
+        .max(lower_bound)
+        .min(upper_bound) as u32
+}
+
+// Number of likes a tx needs to exceed (or reach, see TieRule) to be liked: the fraction random_number/u32::MAX of number_of_queries
+// (the queries that got a response), rounded down, so it is always in 0..=number_of_queries
+pub fn threshold(random_number: u32, number_of_queries: usize) -> usize {
+    ((random_number as u128 * number_of_queries as u128)/(u32::MAX as u128)) as usize
+}
+
+// Whether a tx with likes out of responses is liked in a round with the given random number:
+// likes are compared to the threshold, and tie decides the case where they are equal
+pub fn is_liked(likes: usize, responses: usize, random_number: u32, tie: TieRule) -> bool {
+    let threshold = threshold(random_number, responses);
+    match tie {
+        TieRule::Dislike => likes > threshold,
+        TieRule::Like => likes >= threshold,
+    }
+}
+
+// Checks the parameters of a tx graph type with tx_count txs (see DatabaseError), including each shard of a union
+fn validate_tx_graph(tx_graph_type: &TxGraphType, tx_count: usize) -> Result<(), DatabaseError> {
+    match tx_graph_type {
+        TxGraphType::Cycle if tx_count < 3 => {
+            Err(DatabaseError::TooFewTransactions { tx_count, minimum: 3 })
+        },
+        TxGraphType::Wheel if tx_count < 4 => {
+            Err(DatabaseError::TooFewTransactions { tx_count, minimum: 4 })
+        },
+        TxGraphType::RandomConflict(p) if !(0.0..=1.0).contains(p) => {
+            Err(DatabaseError::InvalidProbability(*p))
+        },
+        TxGraphType::Clusters { clusters, size } if clusters * size != tx_count => {
+            Err(DatabaseError::TxCountMismatch { expected: clusters * size, tx_count })
+        },
+        TxGraphType::Bipartite { left, right } if left + right != tx_count => {
+            Err(DatabaseError::TxCountMismatch { expected: left + right, tx_count })
+        },
+        TxGraphType::Union(shards) => {
+            let expected = shards.iter().map(|shard| shard.tx_count).sum::<usize>();
+            if expected != tx_count {
+                return Err(DatabaseError::TxCountMismatch { expected, tx_count });
+            }
+            shards.iter().try_for_each(|shard| {
+                if shard.tx_count == 0 {
+                    return Err(DatabaseError::NoTransactions);
+                }
+                validate_tx_graph(&shard.graph_type, shard.tx_count)
+            })
+        },
+        _ => Ok(()),
+    }
+}
+
+fn generate_conflict_graph(tx_graph_type: &TxGraphType, tx_count: usize, rng: &mut impl RngCore) -> (Vec<(TxId, TxGlobalStatus)>, Vision) {
+    match tx_graph_type {
+        TxGraphType::Complete => generate_complete_conflict_graph(tx_count, rng),
+        TxGraphType::Star => generate_star_conflict_graph(tx_count, rng),
+        TxGraphType::Path => generate_path_conflict_graph(tx_count, rng),
+        TxGraphType::Cycle => generate_cycle_conflict_graph(tx_count, rng),
+        TxGraphType::Wheel => generate_wheel_conflict_graph(tx_count, rng),
+        TxGraphType::RandomConflict(p) => generate_random_conflict_graph(tx_count, *p, rng),
+        TxGraphType::Clusters { clusters, size } => generate_clustered_conflict_graph(*clusters, *size, rng),
+        TxGraphType::Bipartite { left, right } => generate_bipartite_conflict_graph(*left, *right, rng),
+        TxGraphType::Union(shards) => generate_union_conflict_graph(shards, rng),
+    }
+}
+
+// Generates each shard in turn and concatenates them; a shard whose txs collide 
+// with the txs of a previous shard is generated again, so the shards are disjoint
+fn generate_union_conflict_graph(shards: &[TxGraphSpec], rng: &mut impl RngCore) -> (Vec<(TxId, TxGlobalStatus)>, Vision) {
+    let mut tx_set: Vec<(TxId, TxGlobalStatus)> = Vec::new();
+    let mut common_preliminary_vision = Vision::new();
+    for shard in shards {
+        let (shard_tx_set, shard_vision) = loop {
+            let (shard_tx_set, shard_vision) = generate_conflict_graph(&shard.graph_type, shard.tx_count, rng);
+            if !shard_tx_set.iter().any(|(tx, _)| common_preliminary_vision.contains(tx)) {
+                break (shard_tx_set, shard_vision);
+            }
+        };
+        for (tx, _) in &shard_tx_set {
+            let conflicts = shard_vision.get_conflict_set(tx).iter().copied().collect::<Vec<TxId>>();
+            common_preliminary_vision.add_tx(*tx, &conflicts, Opinion::None);
+        }
+        tx_set.extend(shard_tx_set);
+    }
+
+    (tx_set, common_preliminary_vision)
+}
+
+// Builds the conflict graph from an explicit list of txs and conflicting pairs, 
+// adding each edge in both directions
+fn generate_conflict_graph_from_edges(txs: &[TxId], edges: &[(TxId, TxId)]) -> Result<(Vec<(TxId, TxGlobalStatus)>, Vision), DatabaseError> {
+    let mut conflicts = BTreeMap::new();
+    for tx in txs {
+        if conflicts.insert(*tx, BTreeSet::new()).is_some() {
+            return Err(DatabaseError::DuplicateTx(*tx));
+        }
+    }
+    for (a, b) in edges {
+        if a == b {
+            return Err(DatabaseError::SelfConflict(*a));
+        }
+        conflicts.get_mut(a).ok_or(DatabaseError::UnknownTx(*a))?.insert(*b);
+        conflicts.get_mut(b).ok_or(DatabaseError::UnknownTx(*b))?.insert(*a);
+    }
+
+    let tx_set = txs.iter()
+        .map(|tx| (*tx, TxGlobalStatus::NotFinalized))
+        .collect::<Vec<(TxId, TxGlobalStatus)>>();
+    let common_preliminary_vision = conflicts.into_iter()
+        .map(|(tx, conflict_set)| (tx, (Conflicts::new_from(&conflict_set.into_iter().collect::<Vec<TxId>>()), Opinion::None)))
+        .collect::<BTreeMap<TxId, (Conflicts, Opinion)>>();
+
+    Ok((tx_set, Vision::new_from(&common_preliminary_vision)))
+}
+
+fn generate_complete_conflict_graph(tx_count: usize, rng: &mut impl RngCore) -> (Vec<(TxId, TxGlobalStatus)>, Vision) {
+
+    // ids are distinct, so that no tx overwrites another one in the vision
+    let tx_id_set = TxId::generate_batch(tx_count, rng);
+    let tx_set = tx_id_set.clone().into_iter()
+        .zip( vec![TxGlobalStatus::NotFinalized; tx_count] )
+        .collect::<Vec<(TxId, TxGlobalStatus)>>();
+
+    let mut common_preliminary_vision = BTreeMap::new();
+    for i in 0..tx_count {
+        let mut conflicts = tx_id_set.clone();
+        let element = conflicts.remove(i);
+        let conflict_set = Conflicts::new_from(&conflicts);
+        common_preliminary_vision.insert(element, (conflict_set, Opinion::None));
+    }
+
+    let common_preliminary_vision = Vision::new_from(&common_preliminary_vision);
+
+    (tx_set, common_preliminary_vision)
+}
+
+fn generate_star_conflict_graph(tx_count: usize, rng: &mut impl RngCore) -> (Vec<(TxId, TxGlobalStatus)>, Vision) {
+
+    let tx_id_set = TxId::generate_batch(tx_count, rng);
+    let tx_set = tx_id_set.clone().into_iter()
+        .zip( vec![TxGlobalStatus::NotFinalized; tx_count] )
+        .collect::<Vec<(TxId, TxGlobalStatus)>>();
+
+    let mut common_preliminary_vision = BTreeMap::new();
+    let center = tx_id_set[0];
+    let mut leaves = tx_id_set.clone();
+    leaves.remove(0);
+    let leaves = Conflicts::new_from(&leaves);
+
+    common_preliminary_vision.insert(center, (leaves, Opinion::None));
+    let center = Conflicts::new_from(&[center]);
+
+    for id in tx_id_set.iter().skip(1) {
+        common_preliminary_vision.insert(*id, (center.clone(), Opinion::None));
+    }
+
+    let common_preliminary_vision = Vision::new_from(&common_preliminary_vision);
+
+    (tx_set, common_preliminary_vision)
+}
+
+fn generate_path_conflict_graph(tx_count: usize, rng: &mut impl RngCore) -> (Vec<(TxId, TxGlobalStatus)>, Vision) {
+
+    let tx_id_set = TxId::generate_batch(tx_count, rng);
+    let tx_set = tx_id_set.clone().into_iter()
+        .zip( vec![TxGlobalStatus::NotFinalized; tx_count] )
+        .collect::<Vec<(TxId, TxGlobalStatus)>>();
+
+    let mut common_preliminary_vision = BTreeMap::new();
+    for i in 0..tx_count {
+        // the endpoints only have one neighbor in the path
+        let mut conflicts = Vec::new();
+        if i > 0 { conflicts.push(tx_id_set[i-1]); }
+        if i+1 < tx_count { conflicts.push(tx_id_set[i+1]); }
+        common_preliminary_vision.insert(tx_id_set[i], (Conflicts::new_from(&conflicts), Opinion::None));
+    }
+
+    let common_preliminary_vision = Vision::new_from(&common_preliminary_vision);
+
+    (tx_set, common_preliminary_vision)
+}
+
+// tx_count must be at least 3
+fn generate_cycle_conflict_graph(tx_count: usize, rng: &mut impl RngCore) -> (Vec<(TxId, TxGlobalStatus)>, Vision) {
+
+    let tx_id_set = TxId::generate_batch(tx_count, rng);
+    let tx_set = tx_id_set.clone().into_iter()
+        .zip( vec![TxGlobalStatus::NotFinalized; tx_count] )
+        .collect::<Vec<(TxId, TxGlobalStatus)>>();
+
+    let mut common_preliminary_vision = BTreeMap::new();
+    for i in 0..tx_count {
+        let conflicts = [tx_id_set[(i+tx_count-1) % tx_count], tx_id_set[(i+1) % tx_count]];
+        common_preliminary_vision.insert(tx_id_set[i], (Conflicts::new_from(&conflicts), Opinion::None));
+    }
+
+    let common_preliminary_vision = Vision::new_from(&common_preliminary_vision);
+
+    (tx_set, common_preliminary_vision)
+}
+
+// tx 0 is the center and conflicts with all the others (the leaves), 
+// and leaf i conflicts with the previous and the next leaves of the ring
+fn generate_wheel_conflict_graph(tx_count: usize, rng: &mut impl RngCore) -> (Vec<(TxId, TxGlobalStatus)>, Vision) {
+
+    let tx_id_set = TxId::generate_batch(tx_count, rng);
+    let tx_set = tx_id_set.clone().into_iter()
+        .zip( vec![TxGlobalStatus::NotFinalized; tx_count] )
+        .collect::<Vec<(TxId, TxGlobalStatus)>>();
+
+    let mut common_preliminary_vision = BTreeMap::new();
+    let center = tx_id_set[0];
+    let leaves = &tx_id_set[1..];
+    common_preliminary_vision.insert(center, (Conflicts::new_from(leaves), Opinion::None));
+
+    let leaf_count = leaves.len();
+    for i in 0..leaf_count {
+        let conflicts = [center, leaves[(i+leaf_count-1) % leaf_count], leaves[(i+1) % leaf_count]];
+        common_preliminary_vision.insert(leaves[i], (Conflicts::new_from(&conflicts), Opinion::None));
+    }
+
+    let common_preliminary_vision = Vision::new_from(&common_preliminary_vision);
+
+    (tx_set, common_preliminary_vision)
+}
+
+// Each unordered pair of txs conflicts independently with probability p
+fn generate_random_conflict_graph(tx_count: usize, p: f64, rng: &mut impl RngCore) -> (Vec<(TxId, TxGlobalStatus)>, Vision) {
+
+    let tx_id_set = TxId::generate_batch(tx_count, rng);
+    let tx_set = tx_id_set.clone().into_iter()
+        .zip( vec![TxGlobalStatus::NotFinalized; tx_count] )
+        .collect::<Vec<(TxId, TxGlobalStatus)>>();
+
+    let mut conflicts = vec![Conflicts::new(); tx_count];
+    for i in 0..tx_count {
+        for j in i+1..tx_count {
+            if rng.gen_bool(p) {
+                conflicts[i].add(tx_id_set[j]);
+                conflicts[j].add(tx_id_set[i]);
+            }
+        }
+    }
+
+    let common_preliminary_vision = tx_id_set.iter().copied()
+        .zip(conflicts.into_iter().map(|conflict_set| (conflict_set, Opinion::None)))
+        .collect::<BTreeMap<TxId, (Conflicts, Opinion)>>();
+    let common_preliminary_vision = Vision::new_from(&common_preliminary_vision);
+
+    (tx_set, common_preliminary_vision)
+}
+
+// Generates clusters disjoint complete conflict graphs with size txs each:
+// txs conflict with every other tx of their cluster, and with no tx of other clusters
+fn generate_clustered_conflict_graph(clusters: usize, size: usize, rng: &mut impl RngCore) -> (Vec<(TxId, TxGlobalStatus)>, Vision) {
+
+    let tx_count = clusters * size;
+    let tx_id_set = TxId::generate_batch(tx_count, rng);
+    let tx_set = tx_id_set.clone().into_iter()
+        .zip( vec![TxGlobalStatus::NotFinalized; tx_count] )
+        .collect::<Vec<(TxId, TxGlobalStatus)>>();
+
+    let mut common_preliminary_vision = BTreeMap::new();
+    for cluster in tx_id_set.chunks(size) {
+        for i in 0..cluster.len() {
+            let mut conflicts = cluster.to_vec();
+            let element = conflicts.remove(i);
+            common_preliminary_vision.insert(element, (Conflicts::new_from(&conflicts), Opinion::None));
+        }
+    }
+
+    let common_preliminary_vision = Vision::new_from(&common_preliminary_vision);
+
+    (tx_set, common_preliminary_vision)
+}
+
+fn generate_bipartite_conflict_graph(left: usize, right: usize, rng: &mut impl RngCore) -> (Vec<(TxId, TxGlobalStatus)>, Vision) {
+
+    let tx_count = left + right;
+    let tx_id_set = TxId::generate_batch(tx_count, rng);
+    let tx_set = tx_id_set.clone().into_iter()
+        .zip( vec![TxGlobalStatus::NotFinalized; tx_count] )
+        .collect::<Vec<(TxId, TxGlobalStatus)>>();
+
+    let (left_txs, right_txs) = tx_id_set.split_at(left);
+    let mut common_preliminary_vision = BTreeMap::new();
+    for tx in left_txs {
+        common_preliminary_vision.insert(*tx, (Conflicts::new_from(right_txs), Opinion::None));
+    }
+    for tx in right_txs {
+        common_preliminary_vision.insert(*tx, (Conflicts::new_from(left_txs), Opinion::None));
+    }
+
+    let common_preliminary_vision = Vision::new_from(&common_preliminary_vision);
+
+    (tx_set, common_preliminary_vision)
+}
+
+pub enum LikeDistributions{
+    // each tx is initially liked by the same number of honest nodes (up to one)
+    Equal,
+    // the initial likes are equally distributed among the given number of txs
+    Concentrated(usize),
+    // each honest node initially likes a random maximal independent set of txs
+    UniformIndependent,
+    // each honest node initially likes each tx (in the order of generation) with the given probability,
+    // and the sampled likes are then repaired to be independent
+    Weighted(Vec<f64>)
+}
+
+
+// Regular: honest node, answers queries with its current opinion
+// Faulty: crashed node, never answers queries; a query sent to it gets no response
+// and is not counted in the threshold of the querying node
+// Malicious: answers every query, but adversarially, according to its AdversaryStrategy
+// (shared by all malicious nodes)
+// Observer: queries other nodes every round like an honest node, starting with no opinions, 
+// but never answers queries (like a faulty node) and is not counted in finalization or agreement rates
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone)]
+pub enum NodeType {
+    Malicious(#[cfg_attr(feature = "serde", serde(with = "crate::adversary::serde_strategy"))] Arc<dyn AdversaryStrategy>), 
+    Faulty,
+    Regular,
+    Observer
+}
+
+// Two malicious nodes have the same type only if they share the same strategy instance
+impl PartialEq for NodeType {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Malicious(a), Self::Malicious(b)) => Arc::ptr_eq(a, b),
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn honest_nodes(database: &Database) -> Vec<&Node> {
+        database.data.values()
+            .filter(|node| node.is_honest())
+            .collect()
+    }
+
+    // Honest node with the given vision and no neighbors
+    fn test_node(vision: Vision) -> Node {
+        Node {
+            id: NodeId::from_u32(0),
+            vision,
+            neighborhood: Neighborhood::new(),
+            status: NodeStatus::NotFinalized,
+            node_type: NodeType::Regular,
+            weight: 1,
+            query_count: None,
+            reliability: 1.0,
+            skip_prob: 0.0,
+            last_changes: BTreeMap::new(),
+            flip_counts: BTreeMap::new(),
+            rng: ChaCha12Rng::seed_from_u64(0),
+        }
+    }
+
+    // Complete conflict graph over the txs 0..tx_count
+    fn complete_vision(tx_count: u32) -> Vision {
+        let txs = (0..tx_count).map(TxId::from_u32).collect::<Vec<TxId>>();
+        let edges = txs.iter()
+            .flat_map(|a| txs.iter().filter(move |b| a < *b).map(move |b| (*a, *b)))
+            .collect::<Vec<(TxId, TxId)>>();
+        generate_conflict_graph_from_edges(&txs, &edges).unwrap().1
+    }
+
+    #[test]
+    fn agreement_rate_stays_in_unit_interval_with_malicious_majority() {
+        let config = FpcsConfig { n: 10, t: 4, ..FpcsConfig::default() };
+        let mut database = DatabaseBuilder::new()
+            .config(&config)
+            .malicious(7)
+            .seed(1)
+            .build()
+            .unwrap();
+
+        // the malicious nodes like every tx, so they hold more likes than there are honest nodes
+        let malicious_nodes = database.node_set.iter()
+            .filter(|(_, node_type, _)| matches!(node_type, NodeType::Malicious(_)))
+            .map(|(id, _, _)| *id)
+            .collect::<Vec<NodeId>>();
+        for node in malicious_nodes {
+            for tx in database.tx_ids() {
+                database.set_node_opinion(node, tx, Opinion::Pending(true, 0), true).unwrap();
+            }
+        }
+
+        let honest_nodes = honest_nodes(&database);
+        for tx in database.tx_ids() {
+            let rate = agreement_rate(&tx, &honest_nodes);
+            assert!((0.5..=1.0).contains(&rate), "agreement rate {rate} of {tx}");
+        }
+
+        database.run_until_final(50);
+        for (_, rate) in database.cluster_agreement_rates() {
+            assert!((0.5..=1.0).contains(&rate), "agreement rate {rate}");
+        }
+        for metrics in database.metrics_history() {
+            assert!(metrics.agreement_rates().values().all(|rate| (0.5..=1.0).contains(rate)));
+        }
+    }
+
+    #[test]
+    fn hashed_tx_id_ties_fall_back_to_the_ids() {
+        // OrderHash::Fnv ignores the random number, so both hashes tie
+        let a = HashedTxId { id: TxId::from_u32(7), random_number: 1, order_hash: OrderHash::Fnv };
+        let b = HashedTxId { random_number: 2, ..a };
+        assert_eq!(a.hash_value(), b.hash_value());
+        assert_eq!(a.cmp(&b), std::cmp::Ordering::Less);
+        assert_eq!(b.cmp(&a), std::cmp::Ordering::Greater);
+
+        let c = HashedTxId { id: TxId::from_u32(8), random_number: 1, order_hash: OrderHash::Identity };
+        let d = HashedTxId { id: TxId::from_u32(7), ..c };
+        assert_eq!(c.cmp(&d), std::cmp::Ordering::Greater);
+        assert_eq!(c.cmp(&c), std::cmp::Ordering::Equal);
+    }
+
+    #[test]
+    fn elim_output_does_not_depend_on_the_input_order() {
+        let node = test_node(complete_vision(16));
+        let txs = (0..16).map(TxId::from_u32).collect::<Vec<TxId>>();
+        for order_hash in [OrderHash::Default, OrderHash::Fnv, OrderHash::Identity] {
+            let mut forward = txs.iter().map(|tx| (*tx, true)).collect::<Vec<(TxId, bool)>>();
+            let mut backward = forward.iter().rev().copied().collect::<Vec<(TxId, bool)>>();
+            let mut shuffled = forward.clone();
+            shuffled.shuffle(&mut ChaCha12Rng::seed_from_u64(3));
+
+            let removed = node.elim(&mut forward, 42, order_hash, &BTreeSet::new());
+            assert_eq!(node.elim(&mut backward, 42, order_hash, &BTreeSet::new()), removed);
+            assert_eq!(node.elim(&mut shuffled, 42, order_hash, &BTreeSet::new()), removed);
+            assert_eq!(forward, backward);
+            assert_eq!(forward, shuffled);
+            // in a complete conflict graph, exactly one tx survives
+            assert_eq!(removed, txs.len() - 1);
+            assert_eq!(forward.iter().filter(|(_, like)| *like).count(), 1);
+        }
+    }
+
+    #[test]
+    fn malicious_echo_of_no_opinion_is_an_abstention() {
+        // tx 0 conflicts with txs 1 and 2; the only honest node (0) likes txs 1 and 2 and has no opinion on tx 0,
+        // so the two malicious nodes (echoing the querier) like txs 1 and 2 and abstain on tx 0
+        let config = FpcsConfig { n: 3, tie: TieRule::Like, order_hash: OrderHash::Identity, ..FpcsConfig::default() };
+        let txs = [0, 1, 2].map(TxId::from_u32);
+        let opinions = BTreeMap::from([(NodeId::from_u32(0), BTreeMap::from([
+            (txs[1], Opinion::Pending(true, 0)),
+            (txs[2], Opinion::Pending(true, 0)),
+        ]))]);
+        let mut database = DatabaseBuilder::new()
+            .config(&config)
+            .malicious(2)
+            .conflict_edges(&txs, &[(txs[0], txs[1]), (txs[0], txs[2])])
+            .sequential_ids(true)
+            .initial_opinions(opinions)
+            .build()
+            .unwrap();
+
+        // with the highest random number, a tx is liked only if all its responses are likes:
+        // tx 0 has no responses, so it is liked, and elim (from the largest id) drops txs 2 and 1 instead;
+        // two dislikes on tx 0 would have left it disliked
+        database.run_fpcs_round_with(u32::MAX);
+        let opinions = database.node_opinions(NodeId::from_u32(0)).unwrap();
+        assert_eq!(opinions, vec![
+            (txs[0], Opinion::Pending(true, 0)),
+            (txs[1], Opinion::Pending(false, 0)),
+            (txs[2], Opinion::Pending(false, 0)),
+        ]);
+    }
+
+    #[test]
+    fn conflicting_txs_racing_to_finalize() {
+        let l = 5;
+        let (a, b) = (TxId::from_u32(0), TxId::from_u32(1));
+
+        // both txs are ready to be finalized in the same batch: the first one wins and finalizes the other as disliked
+        let mut node = test_node(complete_vision(2));
+        node.vision.set_opinion(&a, Opinion::Pending(true, l-1));
+        node.vision.set_opinion(&b, Opinion::Pending(true, l-1));
+        let changes = node.update_opinions(vec![(a, true), (b, true)], l, &BTreeMap::new(), 1);
+        assert!(changes.safety_violations.is_empty());
+        assert_eq!(changes.finalized, 1);
+        assert_eq!(*node.vision.get_opinion_status(&a), Opinion::Final(true));
+        assert_eq!(*node.vision.get_opinion_status(&b), Opinion::Final(false));
+        assert_eq!(node.status, NodeStatus::Finalized);
+
+        // the conflict won the race in an earlier batch: it keeps its final like, and the tx is finalized as disliked
+        let mut node = test_node(complete_vision(2));
+        node.vision.set_opinion(&a, Opinion::Pending(true, l-1));
+        node.vision.set_opinion(&b, Opinion::Final(true));
+        let changes = node.update_opinions(vec![(a, true)], l, &BTreeMap::new(), 1);
+        assert!(changes.safety_violations.is_empty());
+        assert_eq!(*node.vision.get_opinion_status(&a), Opinion::Final(false));
+        assert_eq!(*node.vision.get_opinion_status(&b), Opinion::Final(true));
+        assert_eq!(node.find_safety_violation(), None);
+    }
+
+    #[test]
+    fn check_safety_reports_conflicting_final_likes() {
+        let txs = [0, 1].map(TxId::from_u32);
+        let config = FpcsConfig { n: 3, ..FpcsConfig::default() };
+        let mut database = Database::from_initial_opinions(&config, NodeGraphType::Complete, &txs, &[(txs[0], txs[1])], BTreeMap::new()).unwrap();
+        assert_eq!(database.check_safety(), Ok(()));
+
+        let node = NodeId::from_u32(1);
+        database.set_node_opinion(node, txs[1], Opinion::Final(true), false).unwrap();
+        assert_eq!(
+            database.set_node_opinion(node, txs[0], Opinion::Final(true), false),
+            Err(DatabaseError::ConflictingOpinion { node, tx: txs[0], conflict: txs[1] }));
+        database.set_node_opinion(node, txs[0], Opinion::Final(true), true).unwrap();
+        assert_eq!(database.check_safety(), Err(SafetyViolation { node, tx: txs[0], conflict: txs[1] }));
+        assert!(!database.has_consensus());
+    }
+
+    #[test]
+    fn scaled_random_number_stays_in_beta_interval() {
+        let mut rng = ChaCha12Rng::seed_from_u64(0);
+        let extremes = [0, 1, u32::MAX/2, u32::MAX - 1, u32::MAX];
+        for beta in [0.0, 0.1, 1.0/3.0, 0.45, 0.499_999_999] {
+            let draws = (0..10_000).map(|_| rng.next_u32()).chain(extremes);
+            for random_number in draws {
+                let fraction = scale_random_number(random_number, beta) as f64 / u32::MAX as f64;
+                assert!(beta <= fraction && fraction <= 1.0 - beta, "{random_number} scaled to {fraction} with beta {beta}");
+            }
+        }
+        for random_number in (0..1_000).map(|_| rng.next_u32()).chain(extremes) {
+            assert_eq!(scale_random_number(random_number, 0.5), u32::MAX/2);
+        }
+    }
+
+    #[test]
+    fn threshold_boundaries() {
+        for queries in [0, 1, 2, 5, 1_000, usize::MAX] {
+            assert_eq!(threshold(0, queries), 0);
+            assert_eq!(threshold(u32::MAX, queries), queries);
+        }
+        for random_number in [0, 1, u32::MAX/2, u32::MAX] {
+            assert_eq!(threshold(random_number, 0), 0);
+        }
+        assert_eq!(threshold(u32::MAX/2, 2), 0);
+        assert_eq!(threshold(u32::MAX/2 + 1, 2), 1);
+
+        let mut rng = ChaCha12Rng::seed_from_u64(0);
+        for _ in 0..10_000 {
+            let queries = rng.gen_range(0..=100);
+            assert!(threshold(rng.next_u32(), queries) <= queries);
+        }
+    }
+
+    #[test]
+    fn is_liked_at_the_threshold_boundaries() {
+        for tie in [TieRule::Dislike, TieRule::Like] {
+            for responses in [1, 2, 5, 100] {
+                // threshold = 0: any like is enough, and no likes is a tie
+                assert!(is_liked(1, responses, 0, tie));
+                assert_eq!(is_liked(0, responses, 0, tie), tie == TieRule::Like);
+                // threshold = responses: only a unanimous like ties, and nothing exceeds it
+                assert_eq!(is_liked(responses, responses, u32::MAX, tie), tie == TieRule::Like);
+                assert!(!is_liked(responses - 1, responses, u32::MAX, tie));
+            }
+            // no responses: threshold 0 whatever the random number, and 0 likes is a tie
+            for random_number in [0, u32::MAX/2, u32::MAX] {
+                assert_eq!(is_liked(0, 0, random_number, tie), tie == TieRule::Like);
+            }
+            // exact tie in the middle: threshold(u32::MAX/2 + 1, 2) = 1
+            assert!(!is_liked(0, 2, u32::MAX/2 + 1, tie));
+            assert_eq!(is_liked(1, 2, u32::MAX/2 + 1, tie), tie == TieRule::Like);
+            assert!(is_liked(2, 2, u32::MAX/2 + 1, tie));
+        }
+    }
+
+    #[test]
+    fn isolated_node_keeps_its_opinions() {
+        let config = FpcsConfig { n: 5, t: 4, tie: TieRule::Like, ..FpcsConfig::default() };
+        let mut database = DatabaseBuilder::new()
+            .config(&config)
+            .sequential_ids(true)
+            .seed(0)
+            .build()
+            .unwrap();
+        // node 0 is left out of a complete graph of the other nodes
+        let others = (1..5).map(NodeId::from_u32).collect::<Vec<NodeId>>();
+        let adjacency = others.iter()
+            .map(|node| (*node, others.clone()))
+            .collect::<Vec<(NodeId, Vec<NodeId>)>>();
+        database.set_node_graph(&adjacency).unwrap();
+        let isolated = NodeId::from_u32(0);
+        assert!(database.data[&isolated].neighborhood.is_empty());
+
+        let before = database.node_opinions(isolated).unwrap();
+        let neighbor_before = database.node_opinions(others[0]).unwrap();
+        database.run_fpcs_round();
+        assert_eq!(database.node_opinions(isolated).unwrap(), before);
+        assert_ne!(database.node_opinions(others[0]).unwrap(), neighbor_before);
+        // only the other 4 nodes query, each its 3 neighbors
+        assert_eq!(database.metrics_history()[0].query_count, 4 * 3);
+    }
+
+    // Rng that draws each number twice in a row (0, 0, 1, 1, 2, 2, ...), so every id collides once
+    struct RepeatingRng(u32);
+
+    impl RngCore for RepeatingRng {
+        fn next_u32(&mut self) -> u32 {
+            self.0 += 1;
+            self.0 / 2
+        }
+        fn next_u64(&mut self) -> u64 {
+            self.next_u32() as u64
+        }
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            rand_core::impls::fill_bytes_via_next(self, dest)
+        }
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn threshold_policy_with_partial_knowledge() {
+        let config = FpcsConfig { finalization_policy: FinalizationPolicy::Threshold(0.5), ..FpcsConfig::default() };
+        for fraction in [0.0, -0.1, 1.1, f64::NAN] {
+            let config = FpcsConfig { finalization_policy: FinalizationPolicy::Threshold(fraction), ..config };
+            assert!(matches!(DatabaseBuilder::new().config(&config).build(), Err(DatabaseError::InvalidProbability(_))));
+        }
+
+        // a tx can be finalized globally while some honest nodes do not know it or still have a pending opinion on it
+        let mut partial_finals_seen = false;
+        for seed in 0..5 {
+            let mut database = DatabaseBuilder::new()
+                .config(&config)
+                .tx_knowledge(0.7)
+                .seed(seed)
+                .build()
+                .unwrap();
+            for _ in 0..100 {
+                database.step();
+                partial_finals_seen |= database.tx_set.iter()
+                    .filter(|(_, status)| status.finalized())
+                    .any(|(tx, _)| database.data.values()
+                        .filter(|node| node.is_honest())
+                        .any(|node| !node.vision.try_get_opinion_status(tx).is_some_and(|opinion| opinion.is_final())));
+                let summary = database.summary();
+                assert!(summary.outcomes.len() <= database.tx_set.len());
+                if database.is_final() {
+                    break;
+                }
+            }
+
+            let id = database.join_node(NodeType::Regular);
+            let node = &database.data[&id];
+            for (tx, status) in &database.tx_set {
+                if status.finalized() {
+                    assert!(node.vision.get_opinion_status(tx).is_final(), "seed {seed}: {tx}");
+                }
+            }
+        }
+        assert!(partial_finals_seen);
+    }
+
+    #[test]
+    fn joining_after_the_final_opinions_of_a_tx_are_removed() {
+        let config = FpcsConfig { finalization_policy: FinalizationPolicy::Threshold(0.3), ..FpcsConfig::default() };
+        let mut removals_seen = false;
+        for seed in 0..5 {
+            let mut database = DatabaseBuilder::new()
+                .config(&config)
+                .tx_knowledge(0.7)
+                .seed(seed)
+                .build()
+                .unwrap();
+            for _ in 0..100 {
+                database.step();
+                let is_final = |node: &Node, tx: &TxId| node.vision.try_get_opinion_status(tx).is_some_and(|opinion| opinion.is_final());
+                // a globally finalized tx that some honest nodes have not finalized yet
+                let partially_final = database.tx_set.iter()
+                    .filter(|(_, status)| status.finalized())
+                    .map(|(tx, _)| *tx)
+                    .find(|tx| database.data.values().any(|node| node.is_honest() && !is_final(node, tx)));
+                let Some(tx) = partially_final else {
+                    continue;
+                };
+
+                let holders = database.data.values()
+                    .filter(|node| node.is_honest() && is_final(node, &tx))
+                    .map(|node| node.id)
+                    .collect::<Vec<NodeId>>();
+                for id in holders {
+                    database.remove_node(id).unwrap();
+                }
+                let id = database.join_node(NodeType::Regular);
+                // the tx is pending for the new node, or unknown if only the removed nodes knew it
+                if let Some(opinion) = database.data[&id].vision.try_get_opinion_status(&tx) {
+                    assert!(!opinion.is_final(), "seed {seed}: {tx}");
+                    removals_seen = true;
+                }
+                break;
+            }
+        }
+        assert!(removals_seen);
+    }
+
+    #[test]
+    fn safety_margin_counts_all_nodes() {
+        let database = DatabaseBuilder::new()
+            .total_nodes(20)
+            .faulty(2)
+            .malicious(1)
+            .observers(7)
+            .seed(0)
+            .build()
+            .unwrap();
+        assert_eq!(database.safety_margin(), 10.0 / 20.0);
+        // the bound only compares the malicious nodes to the nodes that answer: 1 < BETA * (10 + 1)
+        assert!(database.is_within_safety_bound());
+
+        let config = FpcsConfig { beta: 0.0, ..FpcsConfig::default() };
+        let database = DatabaseBuilder::new().config(&config).seed(0).build().unwrap();
+        assert!(database.is_within_safety_bound());
+        let database = DatabaseBuilder::new().config(&config).malicious(1).seed(0).build().unwrap();
+        assert!(!database.is_within_safety_bound());
+    }
+
+    #[test]
+    fn empty_union_shards_are_rejected() {
+        for empty_shard in [TxGraphType::Star, TxGraphType::Clusters { clusters: 2, size: 0 }] {
+            let shards = vec![
+                TxGraphSpec { graph_type: empty_shard, tx_count: 0 },
+                TxGraphSpec { graph_type: TxGraphType::Complete, tx_count: 20 },
+            ];
+            let result = DatabaseBuilder::new()
+                .tx_count(20)
+                .tx_graph(TxGraphType::Union(shards))
+                .build();
+            assert_eq!(result.err(), Some(DatabaseError::NoTransactions));
+        }
+    }
+
+    #[test]
+    fn generated_node_ids_are_unique() {
+        // RepeatingRng draws 0, 1, 1, 2, 2, ..., so every other id collides with the one before
+        let mut rng = RepeatingRng(0);
+        let mut data = BTreeMap::new();
+        for expected in 0..100 {
+            let node_id = Database::draw_node_id(&mut rng, &data);
+            assert_eq!(node_id, NodeId::from_u32(expected));
+            data.insert(node_id, Node { id: node_id, ..test_node(Vision::new()) });
+        }
+    }
+
+    #[test]
+    fn concentrated_in_no_txs_is_rejected() {
+        let result = DatabaseBuilder::new()
+            .distribution(LikeDistributions::Concentrated(0))
+            .build();
+        assert_eq!(result.err(), Some(DatabaseError::NoLikedTxs));
+        assert!(DatabaseBuilder::new().distribution(LikeDistributions::Concentrated(1)).build().is_ok());
+    }
+
+    #[test]
+    fn generated_tx_ids_are_unique() {
+        let tx_count = 100_000;
+        let mut rng = ChaCha12Rng::seed_from_u64(0);
+        for tx_graph_type in [TxGraphType::Star, TxGraphType::Path, TxGraphType::Cycle, TxGraphType::Clusters { clusters: 25_000, size: 4 }] {
+            let (tx_set, vision) = generate_conflict_graph(&tx_graph_type, tx_count, &mut rng);
+            assert_eq!(tx_set.len(), tx_count);
+            assert_eq!(vision.get_txs().len(), tx_count);
+        }
+
+        let (tx_set, vision) = generate_conflict_graph(&TxGraphType::Complete, 100, &mut RepeatingRng(0));
+        assert_eq!(tx_set.iter().map(|(tx, _)| tx.get_u32()).collect::<Vec<u32>>(), (0..100).collect::<Vec<u32>>());
+        assert_eq!(vision.get_txs().len(), 100);
+        assert!(vision.get_txs().iter().all(|tx| vision.get_conflict_set(tx).get().len() == 99));
+    }
+
+    #[test]
+    fn same_seed_gives_identical_runs() {
+        let build = || DatabaseBuilder::new()
+            .config(&FpcsConfig::default())
+            .faulty(1)
+            .malicious(1)
+            .node_graph(NodeGraphType::Random(0.5))
+            .tx_graph(TxGraphType::RandomConflict(0.3))
+            .seed(11)
+            .build()
+            .unwrap();
+        let (mut a, mut b) = (build(), build());
+        let opinions = |database: &Database| database.data.keys()
+            .map(|id| (*id, database.node_opinions(*id).unwrap()))
+            .collect::<Vec<(NodeId, Vec<(TxId, Opinion)>)>>();
+        assert_eq!(opinions(&a), opinions(&b));
+        for _ in 0..10 {
+            a.run_fpcs_round();
+            b.run_fpcs_round();
+            assert_eq!(opinions(&a), opinions(&b), "round {}", a.round);
+        }
+        assert_eq!(a.metrics_history().len(), 10);
+        assert_eq!(a.metrics_history(), b.metrics_history());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialization_is_deterministic() {
+        let build = || DatabaseBuilder::new()
+            .config(&FpcsConfig::default())
+            .tx_graph(TxGraphType::Complete)
+            .seed(7)
+            .build()
+            .unwrap();
+        let (mut a, mut b) = (build(), build());
+        a.run_fpcs_round();
+        b.run_fpcs_round();
+        let json = a.to_json().unwrap();
+        assert_eq!(json, b.to_json().unwrap());
+        assert_eq!(Database::from_json(&json).unwrap().to_json().unwrap(), json);
+    }
+
+    // New opinions of node computed over all its txs, final ones included, as before final txs were skipped;
+    // the opinions on final txs are then dropped, since update_opinions never changes them
+    fn full_new_opinions(node: &Node, node_sample: &[NodeId], database: &Database, random_number: u32) -> Vec<(TxId, bool)> {
+        let mut auxiliary_opinion = node.vision.get_txs().into_iter()
+            .map(|tx| {
+                let responses = node_sample.iter()
+                    .filter_map(|id| database.data[id].vision.query_confident_opinion(&tx, database.config.response_confidence))
+                    .collect::<Vec<bool>>();
+                let likes = responses.iter().filter(|like| **like).count();
+                (tx, is_liked(likes, responses.len(), random_number, database.config.tie))
+            })
+            .collect::<Vec<(TxId, bool)>>();
+        let order_hash = database.config.order_hash;
+        node.elim(&mut auxiliary_opinion, random_number, order_hash, &BTreeSet::new());
+        let (mut new_opinions, _) = node.comp(auxiliary_opinion, random_number, order_hash, &BTreeSet::new());
+        new_opinions.retain(|(tx, _)| !node.vision.get_opinion_status(tx).is_final());
+        new_opinions.sort();
+        new_opinions
+    }
+
+    #[test]
+    fn skipping_final_txs_matches_the_full_computation() {
+        let mut final_txs_seen = false;
+        for tx_graph_type in [TxGraphType::Star, TxGraphType::Complete, TxGraphType::Path] {
+            // every node queries all the others, so both computations see the same responses
+            let config = FpcsConfig { n: 8, t: 6, k: 8, ..FpcsConfig::default() };
+            let mut database = DatabaseBuilder::new()
+                .config(&config)
+                .tx_graph(tx_graph_type)
+                .distribution(LikeDistributions::UniformIndependent)
+                .seed(5)
+                .build()
+                .unwrap();
+            // the txs finalize at different rounds, so the nodes query a mix of final and pending txs
+            for (index, tx) in database.tx_ids().into_iter().enumerate() {
+                database.set_finalization_threshold(tx, 1 + index as u32).unwrap();
+            }
+            let mut rng = ChaCha12Rng::seed_from_u64(5);
+            while !database.is_final() && database.round() < 50 {
+                let random_number = rng.next_u32();
+                for node in database.data.values().filter(|node| !node.status.finalized()) {
+                    final_txs_seen |= node.vision.get_txs().iter().any(|tx| node.vision.get_opinion_status(tx).is_final());
+                    let node_sample = node.neighborhood.get().clone();
+                    let (mut new_opinions, _) = node.collect_and_set_new_opinion(node_sample.clone(), &database, random_number, random_number);
+                    new_opinions.sort();
+                    assert_eq!(new_opinions, full_new_opinions(node, &node_sample, &database, random_number), "round {}", database.round());
+                }
+                database.run_fpcs_round_with(random_number);
+            }
+        }
+        // in a complete conflict graph, a node finalizes all its txs at once, but not in the others
+        assert!(final_txs_seen);
+    }
+
+    #[test]
+    fn node_rngs_are_distinct_streams_of_the_master_seed() {
+        let seed = 11;
+        let database = DatabaseBuilder::new()
+            .config(&FpcsConfig::default())
+            .sequential_ids(true)
+            .seed(seed)
+            .build()
+            .unwrap();
+        let first_draws = |mut rng: ChaCha12Rng| (0..4).map(|_| rng.next_u64()).collect::<Vec<u64>>();
+
+        // node 0 does not replay the rng of the database, and no two nodes share a stream
+        let mut draws = database.data.values()
+            .map(|node| first_draws(node.rng.clone()))
+            .collect::<BTreeSet<Vec<u64>>>();
+        assert_eq!(draws.len(), database.data.len());
+        assert!(draws.insert(first_draws(ChaCha12Rng::seed_from_u64(seed))));
+
+        let snapshot = database.snapshot_with_seed(12);
+        for node in snapshot.data.values() {
+            assert_eq!(node.rng, Database::node_rng(12, node.id));
+            assert_ne!(node.rng, database.data[&node.id].rng);
+        }
+    }
+}
@@ -0,0 +1,9 @@
+pub mod adversary;
+pub mod aux_types;
+pub mod builder;
+pub mod config;
+pub mod constants;
+pub mod events;
+pub mod export;
+pub mod fpcs;
+pub mod metrics;
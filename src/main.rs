@@ -1,25 +1,19 @@
-use crate::fpcs::{Database, LikeDistributions};
-use crate::aux_types::{NodeGraphType, TxGraphType};
-use crate::constants::{T, N};
+use outro_08::fpcs::{Database, LikeDistributions};
+use outro_08::aux_types::{NodeGraphType, TxGraphType};
+use outro_08::config::FpcsConfig;
+use outro_08::events::print_event;
 
 use std::{thread, time};
 
-mod constants;
-mod fpcs;
-mod aux_types;
-
 fn main(){
-        let mut database = Database::generate_new(N, 0, N-2, NodeGraphType::Complete, T, TxGraphType::Star, LikeDistributions::Concentrated(2));
-        let mut round = 1;
+        let config = FpcsConfig::default();
+        let mut database = Database::generate_new(&config, 0, config.n-2, NodeGraphType::Complete, TxGraphType::Star, LikeDistributions::Concentrated(2)).unwrap();
+        database.set_event_handler(print_event);
         while !database.is_final() {
-            println!("Round {}", round);    
+            println!("Round {}", database.round() + 1);    
             database.run_fpcs_round(); 
             //database.print_results();
             thread::sleep(time::Duration::from_millis(1000));
-            round += 1;
         }
 
 }
-
-//TODO
-// bounds on the constants
@@ -0,0 +1,122 @@
+use crate::aux_types::{NodeId, TxId};
+use crate::fpcs::RunOutcome;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+// Snapshot of the network taken at the end of a round:
+//
+// round: index of the round (starting at 1)
+// like_fractions: for each tx that was not globally finalized when the round started,
+// the fraction of honest nodes that like it at the end of the round
+// query_count: number of queries sent by the honest nodes and observers in the round (including the ones that got no response)
+// finalized_tx_count: number of txs finalized by all honest nodes at the end of the round
+// finalized_node_count: number of honest nodes that finalized all their opinions at the end of the round
+// elim_removed: number of likes dropped by the elim step, over all the nodes that queried in the round
+// comp_added: number of likes added by the comp step, over all the nodes that queried in the round
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoundMetrics {
+    pub round: u32,
+    pub like_fractions: BTreeMap<TxId, f64>,
+    pub query_count: usize,
+    pub finalized_tx_count: usize,
+    pub finalized_node_count: usize,
+    pub elim_removed: usize,
+    pub comp_added: usize,
+}
+
+impl RoundMetrics {
+    // For each tx in like_fractions, the fraction of honest nodes that agree with the majority opinion
+    pub fn agreement_rates(&self) -> BTreeMap<TxId, f64> {
+        self.like_fractions.iter()
+            .map(|(tx, fraction)| (*tx, fraction.max(1.0 - fraction)))
+            .collect()
+    }
+
+    // Minimum of agreement_rates, None if like_fractions is empty
+    pub fn min_agreement_rate(&self) -> Option<f64> {
+        self.agreement_rates().into_values().reduce(f64::min)
+    }
+}
+
+// Summary of the consensus reached by the honest nodes, as returned by Database::summary:
+//
+// finalized_tx_count: number of txs finalized by all honest nodes
+// finalized_honest_node_count: number of honest nodes that finalized all their opinions
+// outcomes: for each tx finalized by all honest nodes, whether the majority of them likes it
+// min_agreement_rate: over the txs finalized by all honest nodes, the minimum fraction of honest nodes
+// that agree with the majority (None if no tx is finalized)
+// cluster_winners: each connected component of the conflict graph, with the tx finalized as liked
+// by all honest nodes in it (None if there is none yet)
+// efficiency: queries sent by the honest nodes and observers per finalized tx (None if no tx is finalized)
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsensusSummary {
+    pub finalized_tx_count: usize,
+    pub finalized_honest_node_count: usize,
+    pub outcomes: BTreeMap<TxId, bool>,
+    pub min_agreement_rate: Option<f64>,
+    pub cluster_winners: Vec<(Vec<TxId>, Option<TxId>)>,
+    pub efficiency: Option<f64>,
+}
+
+// What changed in one round, as returned by Database::step:
+//
+// round: index of the round (starting at 1)
+// flipped_opinions: honest nodes that changed their opinion (like or dislike) on a tx, with the new opinion
+// finalized_txs: txs finalized by all honest nodes in this round
+// finalized_nodes: honest nodes that finalized all their opinions in this round
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoundDelta {
+    pub round: u32,
+    pub flipped_opinions: Vec<(NodeId, TxId, bool)>,
+    pub finalized_txs: Vec<TxId>,
+    pub finalized_nodes: Vec<NodeId>,
+}
+
+// Rounds from the start of the simulation to the global finalization of the txs, 
+// as returned by Database::latency_stats:
+//
+// mean, median, max: over the finalized txs (None if no tx is finalized)
+// unfinalized: txs not finalized by all honest nodes yet, which are left out of the statistics
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct LatencyStats {
+    pub mean: Option<f64>,
+    pub median: Option<f64>,
+    pub max: Option<u32>,
+    pub unfinalized: Vec<TxId>,
+}
+
+// What the network believes about a tx, as returned by Database::ledger:
+//
+// Accepted: finalized by all honest nodes, and liked by the majority of them
+// Rejected: finalized by all honest nodes, and disliked by the majority of them
+// Pending: not finalized by all honest nodes yet
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedgerState {
+    Accepted,
+    Rejected,
+    Pending,
+}
+
+// Result of Database::run_benchmark:
+//
+// outcome: whether all honest nodes finalized, with the number of rounds run
+// total_query_count: number of queries sent by the honest nodes (the only ones that query in a benchmark) over the run
+// elapsed: wall-clock time of the rounds (the generation of the database is not included)
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchResult {
+    pub outcome: RunOutcome,
+    pub total_query_count: u64,
+    pub elapsed: Duration,
+}